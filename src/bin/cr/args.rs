@@ -1,6 +1,9 @@
 //! Argument parsing.
 
-use clap::{Args, Parser, Subcommand};
+use std::ffi;
+use std::path;
+
+use clap::{Args, Parser, Subcommand, ValueEnum};
 
 #[non_exhaustive]
 #[derive(Debug, Parser)]
@@ -31,6 +34,15 @@ pub enum CmdMainSub {
 		args: SubCmdUseArgs,
 	},
 
+	/// Run a command in an environment instead of its shell.
+	///
+	/// Exits with the command's exit code.
+	#[command(arg_required_else_help = true)]
+	Exec {
+		#[command(flatten)]
+		args: SubCmdExecArgs,
+	},
+
 	/// Remove the files and directories created by the `new` sub-command.
 	#[command(arg_required_else_help = true)]
 	Rm {
@@ -43,6 +55,116 @@ pub enum CmdMainSub {
 		#[command(flatten)]
 		args: SubCmdLsArgs,
 	},
+
+	/// Check one or all environments for problems without starting them.
+	///
+	/// Exits with a non-zero status if any errors were found.
+	Check {
+		#[command(flatten)]
+		args: SubCmdCheckArgs,
+	},
+
+	/// Migrate the config of one or all environments to the latest version.
+	///
+	/// The original config is kept next to it as `config.toml.v<VERSION>.bak`.
+	Migrate {
+		#[command(flatten)]
+		args: SubCmdMigrateArgs,
+	},
+
+	/// Print the JSON Schema of config.toml.
+	///
+	/// Editors using taplo (e.g. Even Better TOML) pick it up with a
+	/// `#:schema <FILE>` comment on the first line of config.toml.
+	Schema {
+		#[command(flatten)]
+		args: SubCmdSchemaArgs,
+	},
+
+	/// Get or edit values in an environment's config.toml.
+	///
+	/// Edits keep the comments and formatting of the file and are only written
+	/// if the result is a valid config.
+	#[command(arg_required_else_help = true)]
+	Config {
+		#[command(flatten)]
+		args: SubCmdConfigArgs,
+	},
+
+	/// Edit an environment's config.toml with `$VISUAL` or `$EDITOR`.
+	///
+	/// A copy of the config is edited and the editor is re-opened with the
+	/// error until the copy is valid, saving it unchanged aborts the edit.
+	#[command(arg_required_else_help = true)]
+	Edit {
+		#[command(flatten)]
+		args: SubCmdEditArgs,
+	},
+
+	/// Print the home directory of an environment's shell.
+	///
+	/// Prints the user's home directory if `shell.home` is "host".
+	#[command(arg_required_else_help = true)]
+	Home {
+		#[command(flatten)]
+		args: SubCmdHomeArgs,
+	},
+
+	/// Print or clear the history of an environment's shell.
+	///
+	/// Only works for environments with `shell.history = "isolated"`.
+	#[command(arg_required_else_help = true)]
+	History {
+		#[command(flatten)]
+		args: SubCmdHistoryArgs,
+	},
+
+	/// Allow the config of a project-local environment to be used.
+	///
+	/// The config has to be allowed again whenever it, its rc file or a file
+	/// in `shell.rc.source` or `vars.dotenv` changes.
+	Allow {
+		#[command(flatten)]
+		args: SubCmdAllowArgs,
+	},
+
+	/// Revoke the permission to use the config of a project-local environment.
+	Deny {
+		#[command(flatten)]
+		args: SubCmdDenyArgs,
+	},
+
+	/// Manage the allowed configs of project-local environments.
+	#[command(arg_required_else_help = true)]
+	Trust {
+		#[command(flatten)]
+		args: SubCmdTrustArgs,
+	},
+
+	/// Print a snippet which uses environments automatically in a shell.
+	///
+	/// Add `eval "$(cr hook bash)"` to ~/.bashrc, `eval "$(cr hook zsh)"` to
+	/// ~/.zshrc or `cr hook fish | source` to ~/.config/fish/config.fish. When
+	/// the shell changes to a directory of a project-local environment, or of
+	/// a named environment with `shell.cwd`, the environment's variables and
+	/// PATH are set in the shell and they're reverted when leaving it. Hooks,
+	/// rc files and `[files]` are only used by `cr use`.
+	#[command(arg_required_else_help = true)]
+	Hook {
+		#[command(flatten)]
+		args: SubCmdHookArgs,
+	},
+
+	/// Print the variables an environment's shell would get.
+	///
+	/// Prepares the environment's bin directory like `cr use`, so that the
+	/// output can be used with `eval "$(cr env NAME)"` instead of starting a
+	/// shell. Hooks, rc files and `[files]` are only used by `cr use`.
+	#[command(arg_required_else_help = true)]
+	Env {
+		#[command(flatten)]
+		args: SubCmdEnvArgs,
+	},
 }
 
 #[non_exhaustive]
@@ -56,15 +178,20 @@ pub struct SubCmdNewArgs {
 #[non_exhaustive]
 #[derive(Debug, Args)]
 pub struct SubCmdUseArgs {
-	/// Environment name
+	/// Environment name, or a path for a project-local environment
 	#[arg(value_name = "ENV_NAME")]
 	pub name: String,
+
+	/// Start the shell in this directory instead of `shell.cwd`
+	#[arg(long = "cd", value_name = "DIR")]
+	pub cd: Option<path::PathBuf>,
 }
 
 #[non_exhaustive]
 #[derive(Debug, Args)]
 pub struct SubCmdRmArgs {
-	/// Environment name
+	/// Environment name, or a path for a project-local environment whose data
+	/// is removed, but not its config
 	#[arg(value_name = "ENV_NAME")]
 	pub name: String,
 }
@@ -77,10 +204,243 @@ pub struct SubCmdLsArgs {
 	pub shell: bool,
 }
 
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdCheckArgs {
+	/// Environment name, checks all environments if not given
+	#[arg(value_name = "ENV_NAME")]
+	pub name: Option<String>,
+
+	/// Fail on warnings too
+	#[arg(short = 'W', long = "deny-warnings", default_value_t = false)]
+	pub deny_warnings: bool,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdMigrateArgs {
+	/// Environment name, migrates all environments if not given
+	#[arg(value_name = "ENV_NAME")]
+	pub name: Option<String>,
+
+	/// Only report what would change
+	#[arg(short = 'n', long = "dry-run", default_value_t = false)]
+	pub dry_run: bool,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdSchemaArgs {
+	/// Write the schema to a file instead of stdout
+	#[arg(short = 'o', long = "output", value_name = "FILE")]
+	pub output: Option<path::PathBuf>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdConfigArgs {
+	#[command(subcommand)]
+	pub sub: SubCmdConfigSub,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Subcommand)]
+pub enum SubCmdConfigSub {
+	/// Print the value of a key, including default values.
+	#[command(arg_required_else_help = true)]
+	Get {
+		#[command(flatten)]
+		args: SubCmdConfigKeyArgs,
+	},
+
+	/// Set the value of a key.
+	///
+	/// VALUE is parsed as TOML (e.g. `true`, `42` or `["a", "b"]`) and taken
+	/// as a string if it isn't valid TOML.
+	#[command(arg_required_else_help = true)]
+	Set {
+		#[command(flatten)]
+		args: SubCmdConfigValueArgs,
+	},
+
+	/// Remove a key so that its default value is used.
+	#[command(arg_required_else_help = true)]
+	Unset {
+		#[command(flatten)]
+		args: SubCmdConfigKeyArgs,
+	},
+
+	/// Append a value to an array, creating the array if it isn't set.
+	#[command(arg_required_else_help = true)]
+	Append {
+		#[command(flatten)]
+		args: SubCmdConfigValueArgs,
+	},
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdConfigKeyArgs {
+	/// Environment name
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+
+	/// Dotted key path, e.g. `shell.bin` or `vars.set.EDITOR`
+	#[arg(value_name = "KEY")]
+	pub key: String,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdConfigValueArgs {
+	/// Environment name
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+
+	/// Dotted key path, e.g. `shell.bin` or `vars.set.EDITOR`
+	#[arg(value_name = "KEY")]
+	pub key: String,
+
+	/// TOML value, or a string if it isn't valid TOML
+	#[arg(value_name = "VALUE", allow_hyphen_values = true)]
+	pub value: String,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdEditArgs {
+	/// Environment name
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdHomeArgs {
+	/// Environment name
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdHistoryArgs {
+	/// Environment name
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+
+	/// Delete the history instead of printing it
+	#[arg(short = 'c', long = "clear", default_value_t = false)]
+	pub clear: bool,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdExecArgs {
+	/// Environment name, or a path for a project-local environment
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+
+	/// Run the command in this directory instead of `shell.cwd`
+	#[arg(long = "cd", value_name = "DIR")]
+	pub cd: Option<path::PathBuf>,
+
+	/// Command and its arguments
+	#[arg(last = true, required = true, value_name = "CMD")]
+	pub cmd: Vec<ffi::OsString>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdAllowArgs {
+	/// Directory in the project
+	#[arg(value_name = "DIR", default_value = ".")]
+	pub dir: path::PathBuf,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdDenyArgs {
+	/// Directory in the project
+	#[arg(value_name = "DIR", default_value = ".")]
+	pub dir: path::PathBuf,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdTrustArgs {
+	#[command(subcommand)]
+	pub sub: SubCmdTrustSub,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Subcommand)]
+pub enum SubCmdTrustSub {
+	/// List the allowed configs and whether they changed or were removed
+	/// since.
+	Ls,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdHookArgs {
+	/// Shell to print the snippet for
+	#[arg(value_name = "SHELL")]
+	pub shell: HookShell,
+
+	/// Print the commands which apply the environment of the current
+	/// directory, used by the snippet
+	#[arg(long = "apply", default_value_t = false, hide = true)]
+	pub apply: bool,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum HookShell {
+	Bash,
+	Zsh,
+	Fish,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Args)]
+pub struct SubCmdEnvArgs {
+	/// Environment name, or a path for a project-local environment
+	#[arg(value_name = "ENV_NAME")]
+	pub name: String,
+
+	/// Output format
+	#[arg(
+		short = 'f',
+		long = "format",
+		value_name = "FORMAT",
+		default_value = "posix"
+	)]
+	pub format: EnvFormat,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub enum EnvFormat {
+	/// `export` and `unset` statements for POSIX shells
+	Posix,
+	/// `set -gx` and `set -e` statements for fish
+	Fish,
+	/// A JSON object, non-UTF-8 values are converted lossily and variables
+	/// to unset are null
+	Json,
+	/// `KEY=VALUE` pairs terminated by NUL, without the variables to unset
+	Nul,
+}
+
 #[non_exhaustive]
 #[derive(Debug, Args)]
 #[command(about)]
-pub struct CmdMainArgs;
+pub struct CmdMainArgs {
+	/// Treat unknown keys in config files as errors instead of warnings
+	#[arg(long = "strict", global = true, default_value_t = false)]
+	pub strict: bool,
+}
 
 impl CmdMain {
 	pub fn from_parse() -> Self {
@@ -0,0 +1,429 @@
+//! Validation of environments without starting them.
+//!
+//! The checks mirror what `cr use` does so that problems which would only be
+//! discovered halfway through starting an environment are reported up front.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path;
+
+use crate::dotenv;
+use crate::files;
+use crate::migrate;
+use crate::senv;
+use crate::table;
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+	Error,
+	Warning,
+}
+
+impl fmt::Display for Severity {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Error => write!(f, "error"),
+			Self::Warning => write!(f, "warning"),
+		}
+	}
+}
+
+/// The part of the environment an issue was found in.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Category {
+	Files,
+	Config,
+	Shell,
+	Vars,
+	Bin,
+}
+
+impl fmt::Display for Category {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		match self {
+			Self::Files => write!(f, "files"),
+			Self::Config => write!(f, "config"),
+			Self::Shell => write!(f, "shell"),
+			Self::Vars => write!(f, "vars"),
+			Self::Bin => write!(f, "bin"),
+		}
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct Issue {
+	pub category: Category,
+	pub severity: Severity,
+	pub msg: String,
+}
+
+/// All the issues found for a single environment.
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct Report {
+	pub name: String,
+	pub issues: Vec<Issue>,
+}
+
+impl Report {
+	pub fn new(name: &str) -> Self {
+		Self {
+			name: String::from(name),
+			issues: Vec::new(),
+		}
+	}
+
+	pub fn error(&mut self, category: Category, msg: String) {
+		self.issues.push(Issue {
+			category,
+			severity: Severity::Error,
+			msg,
+		});
+	}
+
+	pub fn warning(&mut self, category: Category, msg: String) {
+		self.issues.push(Issue {
+			category,
+			severity: Severity::Warning,
+			msg,
+		});
+	}
+
+	/// Add an error or a warning depending on `fatal`, used for the config
+	/// options which decide whether a problem stops `cr use`.
+	fn issue(&mut self, fatal: bool, category: Category, msg: String) {
+		if fatal {
+			self.error(category, msg);
+		} else {
+			self.warning(category, msg);
+		}
+	}
+
+	pub fn errors(&self) -> usize {
+		self.issues
+			.iter()
+			.filter(|issue| issue.severity == Severity::Error)
+			.count()
+	}
+
+	pub fn warnings(&self) -> usize {
+		self.issues
+			.iter()
+			.filter(|issue| issue.severity == Severity::Warning)
+			.count()
+	}
+}
+
+impl fmt::Display for Report {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		writeln!(
+			f,
+			"{}: {} error(s), {} warning(s)",
+			self.name,
+			self.errors(),
+			self.warnings()
+		)?;
+
+		let mut issues: Vec<&Issue> = self.issues.iter().collect();
+		issues.sort();
+		for issue in issues {
+			writeln!(
+				f,
+				"  {}[{}]: {}",
+				issue.severity, issue.category, issue.msg
+			)?;
+		}
+
+		Ok(())
+	}
+}
+
+/// Check everything `cr use` would need for the environment.
+/// Unknown keys are errors if `strict` is true.
+pub fn check_env(shell_env: &senv::Senv, strict: bool) -> Report {
+	let mut report = Report::new(&shell_env.name);
+
+	if !check_files(&mut report, shell_env) {
+		return report;
+	}
+
+	let src = match fs::read_to_string(&shell_env.files.cfg_file) {
+		Ok(ok) => ok,
+		Err(err) => {
+			report.error(
+				Category::Files,
+				format!(
+					"Couldn't read '{}': {err}",
+					shell_env.files.cfg_file.display()
+				),
+			);
+			return report;
+		}
+	};
+
+	// Older configs might not match the current schema so check what they'd
+	// be migrated to.
+	let src = match migrate::migrate_str(&src) {
+		Ok(None) => src,
+		Ok(Some(migrated)) => {
+			report.warning(
+				Category::Config,
+				format!(
+					"Config version {} is older than {}, run `cr migrate`",
+					migrated.from, migrated.to
+				),
+			);
+			migrated.src
+		}
+		// Syntax errors are reported with more context by `table::Root::parse`.
+		Err(migrate::Err::Toml(_)) => src,
+		Err(err) => {
+			report.error(Category::Config, err.to_string());
+			return report;
+		}
+	};
+
+	let (env_table, unknown) =
+		match table::Root::parse(&shell_env.files.cfg_file, &src) {
+			Ok(ok) => ok,
+			Err(table::Err::Parse(diag)) => {
+				report.error(
+					Category::Config,
+					format!("{} ({})", diag.msg, diag.location()),
+				);
+				return report;
+			}
+			Err(err) => {
+				report.error(Category::Config, err.to_string());
+				return report;
+			}
+		};
+
+	for diag in unknown {
+		report.issue(
+			strict,
+			Category::Config,
+			format!("{} ({})", diag.msg, diag.location()),
+		);
+	}
+
+	check_table(&mut report, &env_table, &shell_env.files.cfg_dir);
+
+	report
+}
+
+/// Check the parts of the config which depend on the host, `cfg_dir` is the
+/// environment's config directory.
+pub fn check_table(
+	report: &mut Report,
+	env_table: &table::Root,
+	cfg_dir: &path::Path,
+) {
+	check_shell(report, &env_table.shell);
+	check_vars(report, &env_table.vars, cfg_dir);
+	check_bin(report, &env_table.bin);
+	check_sources(report, &env_table.files, cfg_dir);
+}
+
+// Returns whether the config file can be read. Project-local environments only
+// get a data directory once they're used.
+fn check_files(report: &mut Report, shell_env: &senv::Senv) -> bool {
+	let env_files = &shell_env.files;
+	let mut dirs = vec![&env_files.cfg_dir];
+	if !shell_env.project {
+		dirs.push(&env_files.data_dir);
+	}
+	for dir in dirs {
+		match dir.try_exists() {
+			Ok(true) => (),
+			Ok(false) => report.error(
+				Category::Files,
+				format!("Directory '{}' doesn't exist", dir.display()),
+			),
+			Err(err) => report.error(
+				Category::Files,
+				format!("Couldn't access '{}': {err}", dir.display()),
+			),
+		}
+	}
+
+	match env_files.cfg_file.try_exists() {
+		Ok(true) => true,
+		Ok(false) => {
+			report.error(
+				Category::Files,
+				format!(
+					"File '{}' doesn't exist",
+					env_files.cfg_file.display()
+				),
+			);
+			false
+		}
+		Err(err) => {
+			report.error(
+				Category::Files,
+				format!(
+					"Couldn't access '{}': {err}",
+					env_files.cfg_file.display()
+				),
+			);
+			false
+		}
+	}
+}
+
+fn check_shell(report: &mut Report, shell: &table::Shell) {
+	let bin = match files::bin_get_abs(path::Path::new(&shell.bin)) {
+		Ok(ok) => ok,
+		Err(err) => {
+			report.error(Category::Shell, err.to_string());
+			return;
+		}
+	};
+
+	match fs::metadata(&bin) {
+		Ok(meta) => {
+			if !meta.is_file() {
+				report.error(
+					Category::Shell,
+					format!("Shell '{}' isn't a file", bin.display()),
+				);
+			} else if meta.permissions().mode() & 0o111 == 0 {
+				report.error(
+					Category::Shell,
+					format!("Shell '{}' isn't executable", bin.display()),
+				);
+			}
+		}
+		Err(err) => report.error(
+			Category::Shell,
+			format!("Couldn't access shell '{}': {err}", bin.display()),
+		),
+	}
+}
+
+fn check_vars(report: &mut Report, vars: &table::Vars, cfg_dir: &path::Path) {
+	for (var_host, inherit) in vars.inherit_entries() {
+		let var = &inherit.name;
+
+		if table::is_glob(var) || vars.set.contains_key(var) {
+			continue;
+		}
+		if vars.is_denied(&var_host) || vars.is_denied(var) {
+			report.warning(
+				Category::Vars,
+				format!(
+					"Inherited variable '{var}' is always denied by `deny`"
+				),
+			);
+			continue;
+		}
+
+		if std::env::var_os(&var_host).is_none() && inherit.default.is_none() {
+			let mut msg = format!(
+				"Inherited variable '{var_host}' isn't set in the parent \
+				 environment"
+			);
+			if let Some(message) = &inherit.message {
+				msg = format!("{msg}: {message}");
+			}
+			report.issue(vars.is_required(&inherit), Category::Vars, msg);
+		}
+	}
+
+	for dotenv_file in &vars.dotenv {
+		let dotenv_file = files::resolve(dotenv_file, cfg_dir);
+		match dotenv::read(&dotenv_file) {
+			Ok(_) => (),
+			Err(dotenv::Err::Parse(diag)) => report.error(
+				Category::Vars,
+				format!("{} ({})", diag.msg, diag.location()),
+			),
+			Err(err) => report.error(Category::Vars, err.to_string()),
+		}
+	}
+
+	// Commands in `from_cmd` aren't run since they might prompt the user or
+	// have side effects.
+	for (var, from_file) in &vars.from_file {
+		if let Err(err) = from_file.read(var, cfg_dir) {
+			report.error(Category::Vars, err.to_string());
+		}
+	}
+}
+
+fn check_bin(report: &mut Report, bin: &table::Bin) {
+	for dir in &bin.inherit_dirs {
+		if !dir.is_dir() {
+			report.warning(
+				Category::Bin,
+				format!(
+					"Directory '{}' in `inherit_dirs` doesn't exist",
+					dir.display()
+				),
+			);
+		}
+	}
+
+	let inherit = bin.inherit.iter().map(|host_bin| (host_bin, host_bin));
+	for (host_bin, env_bin) in inherit.chain(&bin.inherit_rename) {
+		if env_bin.file_name().is_none() {
+			report.error(
+				Category::Bin,
+				format!(
+					"Binary '{}' has invalid path, terminates with '..'",
+					env_bin.display()
+				),
+			);
+		}
+
+		match files::bin_get_abs(host_bin) {
+			Ok(host_bin_abs) => {
+				if !host_bin_abs.exists() {
+					report.error(
+						Category::Bin,
+						format!(
+							"Binary '{}' doesn't exist on host",
+							host_bin_abs.display()
+						),
+					);
+				}
+			}
+			Err(err) => report.error(Category::Bin, err.to_string()),
+		}
+	}
+}
+
+// The host files used by `[files]`, which are only read on `cr use`.
+fn check_sources(
+	report: &mut Report,
+	env_files: &HashMap<path::PathBuf, table::FileSource>,
+	cfg_dir: &path::Path,
+) {
+	let mut entries: Vec<_> = env_files.iter().collect();
+	entries.sort_by(|lhs, rhs| lhs.0.cmp(rhs.0));
+
+	for (file, source) in entries {
+		let source = match source {
+			table::FileSource::Content(_) => continue,
+			table::FileSource::Template(source)
+			| table::FileSource::Copy(source)
+			| table::FileSource::Link(source) => files::resolve(source, cfg_dir),
+		};
+		if !source.exists() {
+			report.error(
+				Category::Files,
+				format!(
+					"Source '{}' of file '{}' doesn't exist",
+					source.display(),
+					file.display()
+				),
+			);
+		}
+	}
+}
@@ -0,0 +1,31 @@
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::senv;
+use crate::trust;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Trust(#[from] trust::Err),
+}
+
+/// Adds the config of the project-local environment of a directory to the
+/// trust store.
+pub fn cmd_allow(
+	_args_main: &args::CmdMainArgs,
+	args_allow: &args::SubCmdAllowArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_env = senv::Senv::find_project(&args_allow.dir, dirs)?;
+	trust::allow(&shell_env, dirs)?;
+	eprintln!("Allowed '{}'", shell_env.files.cfg_file.display());
+
+	Ok(())
+}
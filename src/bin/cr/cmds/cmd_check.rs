@@ -0,0 +1,58 @@
+use std::io;
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::check;
+use crate::debug::DebugPanic;
+use crate::senv;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+
+	#[error("{0} environment(s) failed the check")]
+	Failed(usize),
+}
+
+/// Validates one or all environments and prints a report for each, returns an
+/// error if any of them has errors (or warnings with `--deny-warnings`).
+pub fn cmd_check(
+	args_main: &args::CmdMainArgs,
+	args_check: &args::SubCmdCheckArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_envs = if let Some(name) = &args_check.name {
+		vec![senv::Senv::from_name(name, dirs)?]
+	} else if dirs.get_config_home().try_exists().dp()? {
+		let mut shell_envs = senv::Senv::get_vec_unchecked(dirs)?;
+		shell_envs.sort();
+		shell_envs
+	} else {
+		Vec::new()
+	};
+
+	let mut failed = 0;
+	for shell_env in shell_envs {
+		let report = check::check_env(&shell_env, args_main.strict);
+		print!("{report}");
+
+		if report.errors() > 0
+			|| (args_check.deny_warnings && report.warnings() > 0)
+		{
+			failed += 1;
+		}
+	}
+
+	if failed > 0 {
+		return Err(Err::Failed(failed));
+	}
+
+	Ok(())
+}
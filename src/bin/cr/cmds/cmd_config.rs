@@ -0,0 +1,230 @@
+use std::fs;
+use std::io;
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::debug::DebugPanic;
+use crate::diag;
+use crate::files;
+use crate::senv;
+use crate::table;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	Files(#[from] files::Err),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Table(#[from] table::Err),
+	#[error(transparent)]
+	Toml(#[from] toml_edit::TomlError),
+	#[error(transparent)]
+	TOMLSerialize(#[from] toml::ser::Error),
+
+	#[error("Key '{0}' isn't set")]
+	NoKey(String),
+	#[error("Key '{0}' isn't a table")]
+	NotTable(String),
+	#[error("Key '{0}' isn't an array")]
+	NotArray(String),
+	#[error("Key path can't be empty")]
+	EmptyKey,
+	#[error("Not writing the config, the result would be invalid:\n{0}")]
+	Invalid(diag::Diags),
+}
+
+/// Gets or edits a value in an environment's config.toml, keeping the
+/// comments and formatting of the rest of the file.
+pub fn cmd_config(
+	_args_main: &args::CmdMainArgs,
+	args_config: &args::SubCmdConfigArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	match &args_config.sub {
+		args::SubCmdConfigSub::Get { args } => get(args, dirs),
+		args::SubCmdConfigSub::Set { args } => {
+			edit(&args.name, &args.key, dirs, |doc, key| {
+				set(doc, key, parse_value(&args.value))
+			})
+		}
+		args::SubCmdConfigSub::Unset { args } => {
+			edit(&args.name, &args.key, dirs, unset)
+		}
+		args::SubCmdConfigSub::Append { args } => {
+			edit(&args.name, &args.key, dirs, |doc, key| {
+				append(doc, key, parse_value(&args.value))
+			})
+		}
+	}
+}
+
+// Prints the effective value, which is the default value if it isn't set in
+// the file.
+fn get(
+	args: &args::SubCmdConfigKeyArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let env_table = table::Root::from_env(&args.name, dirs, false)?;
+	let mut val = toml::Value::try_from(&env_table).dp()?;
+
+	for seg in parse_key(&args.key)? {
+		val = val
+			.get(seg.get())
+			.ok_or(Err::NoKey(args.key.clone()))?
+			.clone();
+	}
+
+	match val {
+		toml::Value::String(string) => println!("{string}"),
+		toml::Value::Table(table) => print!("{}", toml::to_string(&table)?),
+		val => println!("{val}"),
+	}
+
+	Ok(())
+}
+
+// Applies `op` to the environment's config and writes it back if the result is
+// still a valid config.
+fn edit<F>(
+	name: &str,
+	key: &str,
+	dirs: &xdg::BaseDirectories,
+	op: F,
+) -> Result<()>
+where
+	F: FnOnce(&mut toml_edit::DocumentMut, &[toml_edit::Key]) -> Result<()>,
+{
+	let shell_env = senv::Senv::from_name(name, dirs)?;
+	shell_env.is_valid()?;
+
+	let cfg_file = &shell_env.files.cfg_file;
+	let src = fs::read_to_string(cfg_file).dp()?;
+	let mut doc: toml_edit::DocumentMut = src.parse()?;
+
+	op(&mut doc, &parse_key(key)?)?;
+
+	let src = doc.to_string();
+	match table::Root::parse(cfg_file, &src) {
+		Ok((_, unknown)) if unknown.is_empty() => (),
+		Ok((_, unknown)) => return Err(Err::Invalid(diag::Diags(unknown))),
+		Err(table::Err::Parse(diag)) => {
+			return Err(Err::Invalid(diag::Diags(vec![*diag])));
+		}
+		Err(err) => return Err(Err::Table(err)),
+	}
+
+	files::write_atomic(cfg_file, &src)?;
+
+	Ok(())
+}
+
+fn set(
+	doc: &mut toml_edit::DocumentMut,
+	key: &[toml_edit::Key],
+	mut val: toml_edit::Value,
+) -> Result<()> {
+	let (last, parents) = key.split_last().ok_or(Err::EmptyKey)?;
+	let table = table_mut(doc, parents, true)?;
+
+	// Keep the whitespace and comments around a replaced value.
+	if let Some(old) = table.get(last.get()).and_then(|item| item.as_value()) {
+		*val.decor_mut() = old.decor().clone();
+	}
+	table.insert(last.get(), toml_edit::Item::Value(val));
+
+	Ok(())
+}
+
+fn unset(
+	doc: &mut toml_edit::DocumentMut,
+	key: &[toml_edit::Key],
+) -> Result<()> {
+	let (last, parents) = key.split_last().ok_or(Err::EmptyKey)?;
+	let table = table_mut(doc, parents, false)?;
+
+	table
+		.remove(last.get())
+		.ok_or(Err::NoKey(display_key(key)))?;
+
+	Ok(())
+}
+
+fn append(
+	doc: &mut toml_edit::DocumentMut,
+	key: &[toml_edit::Key],
+	val: toml_edit::Value,
+) -> Result<()> {
+	let (last, parents) = key.split_last().ok_or(Err::EmptyKey)?;
+	let table = table_mut(doc, parents, true)?;
+
+	let item = table
+		.entry(last.get())
+		.or_insert(toml_edit::value(toml_edit::Array::new()));
+	item.as_array_mut()
+		.ok_or(Err::NotArray(display_key(key)))?
+		.push(val);
+
+	Ok(())
+}
+
+// The table at `key`, its missing tables are created if `create` is true.
+fn table_mut<'a>(
+	doc: &'a mut toml_edit::DocumentMut,
+	key: &[toml_edit::Key],
+	create: bool,
+) -> Result<&'a mut dyn toml_edit::TableLike> {
+	let mut table: &mut dyn toml_edit::TableLike = doc.as_table_mut();
+
+	for (idx, seg) in key.iter().enumerate() {
+		let path = display_key(&key[..=idx]);
+
+		if table.get(seg.get()).is_none() {
+			if !create {
+				return Err(Err::NoKey(path));
+			}
+			let mut new_table = toml_edit::Table::new();
+			new_table.set_implicit(true);
+			table.insert(seg.get(), toml_edit::Item::Table(new_table));
+		}
+
+		table = table
+			.get_mut(seg.get())
+			.and_then(|item| item.as_table_like_mut())
+			.ok_or(Err::NotTable(path))?;
+	}
+
+	Ok(table)
+}
+
+fn parse_key(key: &str) -> Result<Vec<toml_edit::Key>> {
+	let key = toml_edit::Key::parse(key)?;
+	if key.is_empty() {
+		return Err(Err::EmptyKey);
+	}
+	Ok(key)
+}
+
+fn display_key(key: &[toml_edit::Key]) -> String {
+	key.iter()
+		.map(|seg| seg.display_repr().into_owned())
+		.collect::<Vec<_>>()
+		.join(".")
+}
+
+// Values which aren't valid TOML are taken as strings, so `/bin/bash` doesn't
+// need to be quoted but `"true"` does to be a string.
+fn parse_value(val: &str) -> toml_edit::Value {
+	val.parse::<toml_edit::Value>()
+		.map(|mut val| {
+			val.decor_mut().clear();
+			val
+		})
+		.unwrap_or_else(|_| toml_edit::Value::from(val))
+}
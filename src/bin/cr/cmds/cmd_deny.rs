@@ -0,0 +1,35 @@
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::senv;
+use crate::trust;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Trust(#[from] trust::Err),
+}
+
+/// Removes the config of the project-local environment of a directory from the
+/// trust store.
+pub fn cmd_deny(
+	_args_main: &args::CmdMainArgs,
+	args_deny: &args::SubCmdDenyArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_env = senv::Senv::find_project(&args_deny.dir, dirs)?;
+	let cfg_file = &shell_env.files.cfg_file;
+	if trust::deny(cfg_file, dirs)? {
+		eprintln!("Denied '{}'", cfg_file.display());
+	} else {
+		eprintln!("'{}' wasn't allowed", cfg_file.display());
+	}
+
+	Ok(())
+}
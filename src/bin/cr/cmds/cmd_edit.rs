@@ -0,0 +1,166 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path;
+use std::process;
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::check;
+use crate::debug::DebugPanic;
+use crate::diag;
+use crate::files;
+use crate::senv;
+use crate::table;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	Files(#[from] files::Err),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+
+	#[error("Editor '{0}' exited with {1}, config unchanged")]
+	Editor(String, process::ExitStatus),
+	#[error("Config saved without fixing the error, config unchanged")]
+	Aborted,
+}
+
+// Lines with this prefix at the end of the temporary file describe the error
+// of the previous attempt and are removed before parsing. They're appended so
+// that the line numbers in the error still match the file.
+const ERR_PREFIX: &str = "# cr: ";
+
+/// Opens a copy of the environment's config.toml in the user's editor and only
+/// replaces the config once the copy is valid.
+pub fn cmd_edit(
+	_args_main: &args::CmdMainArgs,
+	args_edit: &args::SubCmdEditArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_env = senv::Senv::from_name(&args_edit.name, dirs)?;
+	shell_env.is_valid()?;
+
+	let cfg_file = &shell_env.files.cfg_file;
+	let orig = fs::read_to_string(cfg_file).dp()?;
+
+	// Kept next to the config so it gets the same permissions and the `.toml`
+	// extension lets editors pick the right syntax.
+	let tmp = TmpFile(
+		shell_env
+			.files
+			.cfg_dir
+			.join(format!(".config.{}.toml", process::id())),
+	);
+	fs::copy(cfg_file, &tmp.0).dp()?;
+
+	let editor = editor();
+	let mut prev = orig.clone();
+	let mut failed = false;
+
+	loop {
+		let status = process::Command::new("/bin/sh")
+			.arg("-c")
+			.arg(format!("{editor} \"$@\""))
+			.arg("sh")
+			.arg(&tmp.0)
+			.status()
+			.dp()?;
+		if !status.success() {
+			return Err(Err::Editor(editor, status));
+		}
+
+		let src = strip_err(&fs::read_to_string(&tmp.0).dp()?);
+		if src == prev {
+			if failed {
+				return Err(Err::Aborted);
+			}
+			println!("No changes to '{}'", cfg_file.display());
+			return Ok(());
+		}
+
+		match validate(cfg_file, &src) {
+			Ok(env_table) => {
+				files::write_atomic(cfg_file, &src)?;
+
+				// Problems with the host don't make the config invalid, so
+				// only mention them.
+				let mut report = check::Report::new(&shell_env.name);
+				check::check_table(
+					&mut report,
+					&env_table,
+					&shell_env.files.cfg_dir,
+				);
+				if !report.issues.is_empty() {
+					eprint!("{report}");
+				}
+				return Ok(());
+			}
+			Err(err) => {
+				eprintln!("{err}");
+				let mut with_err = src;
+				if !with_err.is_empty() && !with_err.ends_with('\n') {
+					with_err.push('\n');
+				}
+				for line in err.lines() {
+					with_err.push_str(&format!("{ERR_PREFIX}{line}\n"));
+				}
+				fs::write(&tmp.0, &with_err).dp()?;
+
+				prev = strip_err(&with_err);
+				failed = true;
+			}
+		}
+	}
+}
+
+// Parse the config, treating unknown keys as errors since they're most likely
+// typos made while editing.
+fn validate(
+	cfg_file: &path::Path,
+	src: &str,
+) -> result::Result<table::Root, String> {
+	match table::Root::parse(cfg_file, src) {
+		Ok((env_table, unknown)) if unknown.is_empty() => Ok(env_table),
+		Ok((_, unknown)) => Err(diag::Diags(unknown).to_string()),
+		Err(err) => Err(err.to_string()),
+	}
+}
+
+fn strip_err(src: &str) -> String {
+	let mut src = src;
+	loop {
+		let body = src.strip_suffix('\n').unwrap_or(src);
+		let start = body.rfind('\n').map_or(0, |idx| idx + 1);
+		if !body[start..].starts_with(ERR_PREFIX) {
+			break;
+		}
+		src = &src[..start];
+	}
+	src.to_owned()
+}
+
+// `$VISUAL`, `$EDITOR` or `vi`, in that order.
+fn editor() -> String {
+	["VISUAL", "EDITOR"]
+		.iter()
+		.filter_map(env::var_os)
+		.map(|editor| editor.to_string_lossy().into_owned())
+		.find(|editor| !editor.trim().is_empty())
+		.unwrap_or_else(|| String::from("vi"))
+}
+
+// Removed when dropped so that it's cleaned up on every return path.
+struct TmpFile(path::PathBuf);
+
+impl Drop for TmpFile {
+	fn drop(&mut self) {
+		let _ = fs::remove_file(&self.0);
+	}
+}
@@ -0,0 +1,80 @@
+use std::collections::BTreeMap;
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::result;
+
+use thiserror::Error;
+
+use super::cmd_use;
+use crate::args;
+use crate::debug::DebugPanic;
+use crate::rc;
+use crate::senv;
+use crate::table::Flavor;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Use(#[from] cmd_use::Err),
+	#[error(transparent)]
+	Json(#[from] serde_json::Error),
+}
+
+/// Prints the variables of the environment, sorted by name, in the requested
+/// format.
+pub fn cmd_env(
+	args_main: &args::CmdMainArgs,
+	args_env: &args::SubCmdEnvArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_env = senv::Senv::from_name(&args_env.name, dirs)?;
+	let vars = cmd_use::env_vars(args_main, &shell_env, dirs)?;
+
+	let mut out = Vec::new();
+	match args_env.format {
+		args::EnvFormat::Posix | args::EnvFormat::Fish => {
+			let flavor = match args_env.format {
+				args::EnvFormat::Fish => Flavor::Fish,
+				_ => Flavor::Sh,
+			};
+			for (var, val) in &vars {
+				out.extend(rc::set_var(flavor, var, val.as_deref()));
+				out.push(b'\n');
+			}
+		}
+		args::EnvFormat::Json => {
+			let vars: BTreeMap<_, _> = vars
+				.iter()
+				.map(|(var, val)| {
+					(
+						var.to_string_lossy(),
+						val.as_ref().map(|val| val.to_string_lossy()),
+					)
+				})
+				.collect();
+			serde_json::to_writer_pretty(&mut out, &vars)?;
+			out.push(b'\n');
+		}
+		args::EnvFormat::Nul => {
+			for (var, val) in &vars {
+				let Some(val) = val else {
+					continue;
+				};
+				out.extend_from_slice(var.as_bytes());
+				out.push(b'=');
+				out.extend_from_slice(val.as_bytes());
+				out.push(0);
+			}
+		}
+	}
+
+	io::stdout().write_all(&out).dp()?;
+	Ok(())
+}
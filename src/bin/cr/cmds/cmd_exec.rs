@@ -0,0 +1,38 @@
+use std::process;
+use std::result;
+
+use thiserror::Error;
+
+use super::cmd_use;
+use crate::args;
+use crate::hooks;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	Use(#[from] cmd_use::Err),
+}
+
+/// Runs a command in the environment like `cr use` runs the shell, and exits
+/// with the command's exit code if it failed.
+pub fn cmd_exec(
+	args_main: &args::CmdMainArgs,
+	args_exec: &args::SubCmdExecArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let status = cmd_use::enter(
+		args_main,
+		&args_exec.name,
+		args_exec.cd.as_deref(),
+		dirs,
+		Some(&args_exec.cmd),
+	)?;
+
+	if !status.success() {
+		process::exit(hooks::exit_code(status));
+	}
+
+	Ok(())
+}
@@ -0,0 +1,66 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::debug::DebugPanic;
+use crate::senv;
+use crate::table;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Table(#[from] table::Err),
+
+	#[error(
+		"Environment '{0}' doesn't have its own history, set `shell.history` \
+		 to \"isolated\""
+	)]
+	NotIsolated(String),
+	#[error("Can't find the history of environment '{0}', `HOME` isn't set")]
+	NoHistoryFile(String),
+}
+
+/// Prints or deletes the history file of the environment's shell.
+pub fn cmd_history(
+	args_main: &args::CmdMainArgs,
+	args_history: &args::SubCmdHistoryArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_env = senv::Senv::from_name(&args_history.name, dirs)?;
+	let env_table = table::Root::from_senv(&shell_env, args_main.strict)?;
+	if env_table.shell.history != table::History::Isolated {
+		return Err(Err::NotIsolated(shell_env.name));
+	}
+
+	let Some(history_file) = env_table.history_file(&shell_env) else {
+		return Err(Err::NoHistoryFile(shell_env.name));
+	};
+	if args_history.clear {
+		match fs::remove_file(&history_file) {
+			Ok(()) => (),
+			Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+			Err(err) => return Err(Err::IO(err)).dp(),
+		}
+		return Ok(());
+	}
+
+	// The shell didn't save anything yet.
+	let history = match fs::read(&history_file) {
+		Ok(history) => history,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+		Err(err) => return Err(Err::IO(err)).dp(),
+	};
+	io::stdout().write_all(&history)?;
+
+	Ok(())
+}
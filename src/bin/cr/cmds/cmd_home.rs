@@ -0,0 +1,43 @@
+use std::env;
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::senv;
+use crate::table;
+use crate::user;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Table(#[from] table::Err),
+
+	#[error("Couldn't find the user's home directory")]
+	NoHome,
+}
+
+/// Prints the home directory of the environment's shell.
+pub fn cmd_home(
+	args_main: &args::CmdMainArgs,
+	args_home: &args::SubCmdHomeArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_env = senv::Senv::from_name(&args_home.name, dirs)?;
+	let env_table = table::Root::from_senv(&shell_env, args_main.strict)?;
+
+	let home_dir = match env_table.home_dir(&shell_env) {
+		Some(home_dir) => home_dir,
+		None => user::current()
+			.map(|passwd| passwd.home)
+			.or_else(|| env::var_os("HOME").map(Into::into))
+			.ok_or(Err::NoHome)?,
+	};
+	println!("{}", home_dir.display());
+
+	Ok(())
+}
@@ -0,0 +1,259 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::ffi;
+use std::io;
+use std::io::Write;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path;
+use std::result;
+
+use thiserror::Error;
+
+use super::cmd_use;
+use crate::args;
+use crate::debug::DebugPanic;
+use crate::rc;
+use crate::senv;
+use crate::table;
+use crate::table::Flavor;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Use(#[from] cmd_use::Err),
+}
+
+/// Variable with the config file of the environment applied to the shell.
+const ENV_VAR: &str = "CLEANROOM_HOOK_ENV";
+/// Variable with the directory the hook last looked for an environment in.
+const DIR_VAR: &str = "CLEANROOM_HOOK_DIR";
+/// Variable with the values the applied environment replaced, hex-encoded.
+const DIFF_VAR: &str = "CLEANROOM_HOOK_DIFF";
+
+/// Prints the snippet for the shell or, with `--apply`, the commands which
+/// switch the shell to the environment of the current directory.
+pub fn cmd_hook(
+	args_main: &args::CmdMainArgs,
+	args_hook: &args::SubCmdHookArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let flavor = match args_hook.shell {
+		args::HookShell::Bash => Flavor::Bash,
+		args::HookShell::Zsh => Flavor::Zsh,
+		args::HookShell::Fish => Flavor::Fish,
+	};
+
+	if args_hook.apply {
+		return apply(args_main, flavor, dirs);
+	}
+
+	let cr = rc::quote(flavor, env::current_exe().dp()?.as_os_str());
+	io::stdout().write_all(&snippet(flavor, &cr)).dp()?;
+	Ok(())
+}
+
+fn snippet(flavor: Flavor, cr: &ffi::OsStr) -> Vec<u8> {
+	let (head, tail) = match flavor {
+		Flavor::Fish => (
+			"function __cleanroom_hook --on-event fish_prompt\n\t",
+			" hook fish --apply | source\nend\n",
+		),
+		Flavor::Zsh => (
+			"_cleanroom_hook() {\n\teval \"$(",
+			" hook zsh --apply)\"\n}\ntypeset -ag precmd_functions\n\
+			 if (( ! ${precmd_functions[(I)_cleanroom_hook]} )); then\n\
+			 \tprecmd_functions=(_cleanroom_hook $precmd_functions)\nfi\n",
+		),
+		Flavor::Sh | Flavor::Bash => (
+			"_cleanroom_hook() {\n\tlocal status=$?\n\teval \"$(",
+			" hook bash --apply)\"\n\treturn $status\n}\n\
+			 if [[ \";${PROMPT_COMMAND:-};\" != *\";_cleanroom_hook;\"* ]]; \
+			 then\n\tPROMPT_COMMAND=\"_cleanroom_hook\
+			 ${PROMPT_COMMAND:+;$PROMPT_COMMAND}\"\nfi\n",
+		),
+	};
+
+	let mut snippet = head.as_bytes().to_vec();
+	snippet.extend_from_slice(cr.as_bytes());
+	snippet.extend_from_slice(tail.as_bytes());
+	snippet
+}
+
+// Reverts the variables of the environment applied before and applies the one
+// of the current directory. Nothing is printed if the directory didn't change
+// since the last time, unless applying failed then.
+fn apply(
+	args_main: &args::CmdMainArgs,
+	flavor: Flavor,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let curr_dir = env::current_dir().dp()?;
+	if env::var_os(DIR_VAR).is_some_and(|dir| dir == curr_dir.as_os_str()) {
+		return Ok(());
+	}
+
+	let mut changes = BTreeMap::new();
+	changes.insert(DIR_VAR.into(), Some(curr_dir.clone().into_os_string()));
+
+	let shell_env = find_env(&curr_dir, dirs)?;
+	let cfg_file = shell_env
+		.as_ref()
+		.map(|shell_env| shell_env.files.cfg_file.as_os_str());
+	if env::var_os(ENV_VAR).as_deref() == cfg_file {
+		print_changes(flavor, &changes)?;
+		return Ok(());
+	}
+
+	// Revert in this process too, so that the environment is computed from the
+	// shell's own variables.
+	for (var, val) in decode(&env::var_os(DIFF_VAR).unwrap_or_default()) {
+		match &val {
+			Some(val) => env::set_var(&var, val),
+			None => env::remove_var(&var),
+		}
+		changes.insert(var, val);
+	}
+	changes.insert(ENV_VAR.into(), None);
+	changes.insert(DIFF_VAR.into(), None);
+
+	let Some(shell_env) = shell_env else {
+		print_changes(flavor, &changes)?;
+		return Ok(());
+	};
+
+	let vars = match cmd_use::env_vars(args_main, &shell_env, dirs) {
+		Ok(vars) => vars,
+		Err(err) => {
+			// Retry on the next prompt, e.g. after `cr allow`.
+			changes.remove(ffi::OsStr::new(DIR_VAR));
+			print_changes(flavor, &changes)?;
+			return Err(Err::Use(err));
+		}
+	};
+
+	let mut diff = Vec::new();
+	for (var, val) in vars {
+		let prev = env::var_os(&var);
+		if prev.as_ref() != val.as_ref() {
+			diff.push((var.clone(), prev));
+			changes.insert(var, val);
+		}
+	}
+	changes.insert(ENV_VAR.into(), Some(shell_env.files.cfg_file.into()));
+	changes.insert(DIFF_VAR.into(), Some(encode(&diff)));
+	eprintln!("Using environment '{}'", shell_env.name);
+
+	print_changes(flavor, &changes)
+}
+
+// The project-local environment of `dir`, or the named environment with the
+// innermost `shell.cwd` containing it. `shell.cwd` can only refer to the
+// `CLEANROOM_*` variables here. The named configs are read without warnings,
+// which would otherwise be printed at every prompt.
+fn find_env(
+	dir: &path::Path,
+	dirs: &xdg::BaseDirectories,
+) -> Result<Option<senv::Senv>> {
+	match senv::Senv::find_project(dir, dirs) {
+		Ok(shell_env) => return Ok(Some(shell_env)),
+		Err(senv::Err::NoProject(_)) => (),
+		Err(err) => return Err(Err::ShellEnv(err)),
+	}
+
+	let mut found: Option<(path::PathBuf, senv::Senv)> = None;
+	for shell_env in senv::Senv::get_vec(dirs)? {
+		if shell_env.project {
+			continue;
+		}
+		if shell_env.is_valid().is_err() {
+			continue;
+		}
+		let Ok(env_table) =
+			table::Root::from_file_quiet(&shell_env.files.cfg_file)
+		else {
+			continue;
+		};
+		let Some(cwd) = &env_table.shell.cwd else {
+			continue;
+		};
+
+		let vars = shell_env
+			.cleanroom_vars()
+			.into_iter()
+			.map(|(var, val)| (var.into(), val))
+			.collect();
+		let Ok(cwd) = env_table.expand_dir(cwd, &shell_env, &vars) else {
+			continue;
+		};
+		let innermost = found
+			.as_ref()
+			.is_none_or(|(found, _)| cwd.starts_with(found));
+		if innermost && cmd_use::is_within(dir, &cwd) {
+			found = Some((cwd, shell_env));
+		}
+	}
+
+	Ok(found.map(|(_, shell_env)| shell_env))
+}
+
+fn print_changes(
+	flavor: Flavor,
+	changes: &BTreeMap<ffi::OsString, Option<ffi::OsString>>,
+) -> Result<()> {
+	let mut out = Vec::new();
+	for (var, val) in changes {
+		out.extend(rc::set_var(flavor, var, val.as_deref()));
+		out.push(b'\n');
+	}
+
+	io::stdout().write_all(&out).dp()?;
+	Ok(())
+}
+
+// Entries are `VAR=value`, or `VAR` if it wasn't set, separated by NUL and
+// hex-encoded since shell variables can't contain NUL.
+fn encode(diff: &[(ffi::OsString, Option<ffi::OsString>)]) -> ffi::OsString {
+	let mut bytes = Vec::new();
+	for (var, val) in diff {
+		bytes.extend_from_slice(var.as_bytes());
+		if let Some(val) = val {
+			bytes.push(b'=');
+			bytes.extend_from_slice(val.as_bytes());
+		}
+		bytes.push(0);
+	}
+
+	bytes
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect::<String>()
+		.into()
+}
+
+fn decode(encoded: &ffi::OsStr) -> Vec<(ffi::OsString, Option<ffi::OsString>)> {
+	let bytes: Vec<u8> = encoded
+		.as_bytes()
+		.chunks(2)
+		.filter_map(|hex| {
+			u8::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+		})
+		.collect();
+
+	bytes
+		.split(|&byte| byte == 0)
+		.filter(|entry| !entry.is_empty())
+		.map(|entry| match entry.iter().position(|&byte| byte == b'=') {
+			Some(idx) => (
+				ffi::OsString::from_vec(entry[..idx].to_vec()),
+				Some(ffi::OsString::from_vec(entry[idx + 1..].to_vec())),
+			),
+			None => (ffi::OsString::from_vec(entry.to_vec()), None),
+		})
+		.collect()
+}
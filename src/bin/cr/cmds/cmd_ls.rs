@@ -20,7 +20,7 @@ pub enum Err {
 /// Lists directories which are considered valid environments (all the needed
 /// files exist).
 pub fn cmd_ls(
-	_args_main: &args::CmdMainArgs,
+	args_main: &args::CmdMainArgs,
 	args_ls: &args::SubCmdLsArgs,
 	dirs: &xdg::BaseDirectories,
 ) -> Result<()> {
@@ -30,7 +30,7 @@ pub fn cmd_ls(
 	let mut rows: Vec<Vec<String>> = Vec::new();
 
 	for shell_env in shell_envs {
-		rows.push(row_from_args(args_ls, &shell_env, dirs)?);
+		rows.push(row_from_args(args_main, args_ls, &shell_env)?);
 	}
 
 	for row in rows {
@@ -41,11 +41,11 @@ pub fn cmd_ls(
 }
 
 fn row_from_args(
+	args_main: &args::CmdMainArgs,
 	args: &args::SubCmdLsArgs,
 	shell_env: &senv::Senv,
-	dirs: &xdg::BaseDirectories,
 ) -> Result<Vec<String>> {
-	let env_table = table::Root::from_env(&shell_env.name, dirs).dp()?;
+	let env_table = table::Root::from_senv(shell_env, args_main.strict).dp()?;
 
 	let mut row: Vec<String> = Vec::new();
 	row.push(shell_env.name.clone());
@@ -0,0 +1,76 @@
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::debug::DebugPanic;
+use crate::migrate;
+use crate::senv;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Migrate(#[from] migrate::Err),
+}
+
+/// Migrates the config of one or all environments and prints what changed for
+/// each.
+pub fn cmd_migrate(
+	_args_main: &args::CmdMainArgs,
+	args_migrate: &args::SubCmdMigrateArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let shell_envs = if let Some(name) = &args_migrate.name {
+		let shell_env = senv::Senv::from_name(name, dirs)?;
+		shell_env.is_valid()?;
+		vec![shell_env]
+	} else {
+		// Project configs belong to their repositories, they're only migrated
+		// when named explicitly.
+		let mut shell_envs = senv::Senv::get_vec(dirs)?;
+		shell_envs.retain(|shell_env| !shell_env.project);
+		shell_envs.sort();
+		shell_envs
+	};
+
+	for shell_env in shell_envs {
+		let migrated = migrate::migrate_file(
+			&shell_env.files.cfg_file,
+			args_migrate.dry_run,
+		)
+		.dp()?;
+
+		let Some(migrated) = migrated else {
+			println!(
+				"{}: up to date (version {})",
+				shell_env.name,
+				migrate::VERSION
+			);
+			continue;
+		};
+
+		println!(
+			"{}: version {} -> {}{}",
+			shell_env.name,
+			migrated.from,
+			migrated.to,
+			if args_migrate.dry_run {
+				" (dry run)"
+			} else {
+				""
+			}
+		);
+		for step in migrated.steps {
+			println!("  - {step}");
+		}
+		if let Some(backup) = migrated.backup {
+			println!("  backup: {}", backup.display());
+		}
+	}
+
+	Ok(())
+}
@@ -5,6 +5,7 @@ use thiserror::Error;
 
 use crate::args;
 use crate::senv;
+use crate::trust;
 
 type Result<T> = result::Result<T, Err>;
 
@@ -14,14 +15,22 @@ pub enum Err {
 	IO(#[from] io::Error),
 	#[error(transparent)]
 	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Trust(#[from] trust::Err),
 }
 
+/// Removes a named environment, or the data and trust entry of a
+/// project-local one, leaving the project itself untouched.
 pub fn cmd_rm(
 	_args_main: &args::CmdMainArgs,
 	args_rm: &args::SubCmdRmArgs,
 	dirs: &xdg::BaseDirectories,
 ) -> Result<()> {
-	senv::Senv::new_xdg(&args_rm.name, dirs)?.rm()?;
+	let shell_env = senv::Senv::from_name(&args_rm.name, dirs)?;
+	if shell_env.project {
+		trust::deny(&shell_env.files.cfg_file, dirs)?;
+	}
+	shell_env.rm()?;
 
 	Ok(())
 }
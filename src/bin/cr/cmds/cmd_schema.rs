@@ -0,0 +1,37 @@
+use std::fs;
+use std::io;
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::debug::DebugPanic;
+use crate::table;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	JSONSerialize(#[from] serde_json::Error),
+}
+
+/// Prints or writes the JSON Schema of `table::Root`.
+pub fn cmd_schema(
+	_args_main: &args::CmdMainArgs,
+	args_schema: &args::SubCmdSchemaArgs,
+	_dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let schema =
+		serde_json::to_string_pretty(&table::Root::json_schema()).dp()?;
+
+	if let Some(output) = &args_schema.output {
+		fs::write(output, schema + "\n").dp()?;
+	} else {
+		println!("{schema}");
+	}
+
+	Ok(())
+}
@@ -0,0 +1,31 @@
+use std::result;
+
+use thiserror::Error;
+
+use crate::args;
+use crate::trust;
+
+type Result<T> = result::Result<T, Err>;
+
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	Trust(#[from] trust::Err),
+}
+
+/// Manages the trust store of project-local configs.
+pub fn cmd_trust(
+	_args_main: &args::CmdMainArgs,
+	args_trust: &args::SubCmdTrustArgs,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	match &args_trust.sub {
+		args::SubCmdTrustSub::Ls => {
+			for (entry, state) in trust::entries(dirs)? {
+				println!("{},{}", entry.cfg_file.display(), state.as_str());
+			}
+		}
+	}
+
+	Ok(())
+}
@@ -1,4 +1,9 @@
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::ffi;
+use std::fs;
 use std::io;
+use std::path;
 use std::process;
 use std::result;
 
@@ -7,8 +12,12 @@ use thiserror::Error;
 use crate::args;
 use crate::debug::{dbgfmt, DebugPanic};
 use crate::files;
+use crate::hooks;
+use crate::materialize;
+use crate::rc;
 use crate::senv;
 use crate::table;
+use crate::trust;
 
 type Result<T> = result::Result<T, Err>;
 
@@ -22,22 +31,158 @@ pub enum Err {
 	IO(#[from] io::Error),
 	#[error(transparent)]
 	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Materialize(#[from] materialize::Err),
+	#[error(transparent)]
+	Rc(#[from] rc::Err),
+	#[error(transparent)]
+	Hooks(#[from] hooks::Err),
+	#[error(transparent)]
+	Trust(#[from] trust::Err),
 
-	#[error("Couldn't convert a `PathBuf` directory to `&str`")]
-	DirToStr,
+	#[error(
+		"Not starting the shell in '{0}', it's outside of `shell.cwd` and \
+		 `shell.cwd_allowed`"
+	)]
+	NotAllowed(path::PathBuf),
+	#[error("Directory '{0}' to start the shell in doesn't exist")]
+	NoCwd(path::PathBuf),
+
+	#[error(
+		"Already in environment '{0}', set `shell.nested` to \"allow\" or \
+		 \"warn\" to use another environment from within it"
+	)]
+	Nested(String),
 }
 
 pub fn cmd_use(
-	_args_main: &args::CmdMainArgs,
+	args_main: &args::CmdMainArgs,
 	args_use: &args::SubCmdUseArgs,
 	dirs: &xdg::BaseDirectories,
 ) -> Result<()> {
-	let shell_env = senv::Senv::new_xdg(&args_use.name, dirs)?;
-	let env_table = table::Root::from_env(&args_use.name, dirs)?;
-	let shell_args = env_table.get_shell_args(&args_use.name, dirs)?;
+	enter(
+		args_main,
+		&args_use.name,
+		args_use.cd.as_deref(),
+		dirs,
+		None,
+	)?;
+	Ok(())
+}
+
+/// Sets up environment `name` and runs its shell, or `cmd` instead if it's
+/// given.
+pub(super) fn enter(
+	args_main: &args::CmdMainArgs,
+	name: &str,
+	cd: Option<&path::Path>,
+	dirs: &xdg::BaseDirectories,
+	cmd: Option<&[ffi::OsString]>,
+) -> Result<process::ExitStatus> {
+	let shell_env = senv::Senv::from_name(name, dirs)?;
+	let env_table = trust::load(&shell_env, dirs, args_main.strict)?;
+	shell_env.register()?;
+	let shell_args = env_table.get_shell_args(&shell_env);
 	dbgfmt!("Using config: {:#?}", env_table);
+
+	if senv::depth() > 0 {
+		let parent = std::env::var(senv::ENV_VAR).unwrap_or_default();
+		match env_table.shell.nested {
+			table::Nested::Allow => (),
+			table::Nested::Warn => eprintln!(
+				"Warning: Using '{}' from within environment '{parent}'",
+				shell_env.name
+			),
+			table::Nested::Deny => return Err(Err::Nested(parent)),
+		}
+	}
 	dbgfmt!("Calling with args: {:?}", shell_args);
 
+	// Check where the shell starts before writing anything for it.
+	let mut shell_env_vars = env_table.to_env(&shell_env)?;
+	let cwd = start_dir(&env_table, &shell_env, cd, &shell_env_vars)?;
+
+	create_dirs(&env_table, &shell_env)?;
+	// The directory can be in the home directory created just now.
+	if let Some(cwd) = &cwd {
+		if !cwd.is_dir() {
+			return Err(Err::NoCwd(cwd.clone()));
+		}
+	}
+	rc::write(&env_table, &shell_env)?;
+
+	let mut shell = match cmd {
+		Some([bin, args @ ..]) => {
+			let mut shell = process::Command::new(bin);
+			shell.args(args);
+			shell
+		}
+		_ => {
+			let mut shell = process::Command::new(&env_table.shell.bin);
+			shell.args(shell_args);
+			shell
+		}
+	};
+	let mut shell = &mut shell;
+	if env_table.vars.inherit_all {
+		for (var, _) in std::env::vars_os() {
+			if env_table.vars.is_denied(&var.to_string_lossy()) {
+				shell = shell.env_remove(var);
+			}
+		}
+	} else {
+		shell = shell.env_clear();
+	}
+
+	materialize::materialize(&env_table, &shell_env, &shell_env_vars)?;
+	if let Some(cwd) = cwd {
+		shell = shell.current_dir(&cwd);
+		shell_env_vars.insert("PWD".into(), cwd.into_os_string());
+	}
+	hooks::run("pre_enter", &env_table.hooks.pre_enter, &shell_env_vars)?;
+	#[allow(clippy::iter_over_hash_type)]
+	for (k, v) in &shell_env_vars {
+		shell = shell.env(k, v);
+	}
+
+	let mut shell = shell.spawn().dp()?;
+	let status = shell.wait().dp()?;
+
+	shell_env_vars.insert(
+		"CLEANROOM_EXIT_CODE".into(),
+		hooks::exit_code(status).to_string().into(),
+	);
+	hooks::run("post_exit", &env_table.hooks.post_exit, &shell_env_vars)?;
+
+	Ok(status)
+}
+
+/// The variables to set in a shell which is already running to enter the
+/// environment, `None` for the ones of `vars.deny` to unset.
+pub(super) fn env_vars(
+	args_main: &args::CmdMainArgs,
+	shell_env: &senv::Senv,
+	dirs: &xdg::BaseDirectories,
+) -> Result<BTreeMap<ffi::OsString, Option<ffi::OsString>>> {
+	let env_table = trust::load(shell_env, dirs, args_main.strict)?;
+	create_dirs(&env_table, shell_env)?;
+
+	let mut vars: BTreeMap<_, _> = env_table
+		.to_env(shell_env)?
+		.into_iter()
+		.map(|(var, val)| (var, Some(val)))
+		.collect();
+	for (var, _) in env::vars_os() {
+		if env_table.vars.is_denied(&var.to_string_lossy()) {
+			vars.entry(var).or_insert(None);
+		}
+	}
+
+	Ok(vars)
+}
+
+/// Recreate the environment's bin directory and create its home directory.
+fn create_dirs(env_table: &table::Root, shell_env: &senv::Senv) -> Result<()> {
 	// Delete `bin` dir and don't return error if it's a "NotFound" error.
 	if shell_env.files.bin_dir.try_exists()? {
 		match std::fs::remove_dir_all(&shell_env.files.bin_dir) {
@@ -54,41 +199,53 @@ pub fn cmd_use(
 	std::fs::create_dir_all(&shell_env.files.bin_dir).dp()?;
 
 	env_table.bin.inherit_bins(&shell_env.files.data_dir)?;
+	env_table.create_home(shell_env)?;
 
-	let mut shell = process::Command::new(env_table.shell.bin);
-	let mut shell = shell.args(shell_args).env_clear();
+	Ok(())
+}
 
-	let shell_env_vars = env_table.vars.to_env()?;
-	#[allow(clippy::iter_over_hash_type)]
-	for (k, v) in shell_env_vars {
-		shell = shell.env(k, v);
-	}
+// The directory to start the shell in, `None` to stay in the current one.
+fn start_dir(
+	env_table: &table::Root,
+	shell_env: &senv::Senv,
+	cd: Option<&path::Path>,
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+) -> Result<Option<path::PathBuf>> {
+	let shell = &env_table.shell;
+	let curr_dir = env::current_dir().dp()?;
+	let cwd = shell
+		.cwd
+		.as_ref()
+		.map(|cwd| env_table.expand_dir(cwd, shell_env, vars))
+		.transpose()?;
+
+	let start_dir = match (cd, &cwd, shell.cwd_policy) {
+		(Some(dir), _, _) => Some(path::absolute(dir).dp()?),
+		(None, Some(cwd), table::CwdPolicy::Always) => Some(cwd.clone()),
+		(None, Some(cwd), table::CwdPolicy::Outside) => {
+			(!is_within(&curr_dir, cwd)).then(|| cwd.clone())
+		}
+		(None, _, _) => None,
+	};
 
-	let mut shell_path = env_table
-		.bin
-		.inherit_dirs
-		.iter()
-		.map(|dir| dir.to_str())
-		.collect::<Option<Vec<_>>>()
-		.ok_or(Err::DirToStr)
-		.dp()?
-		.join(":");
-	if !env_table.bin.inherit.is_empty()
-		|| !env_table.bin.inherit_rename.is_empty()
-		|| env_table.bin.coreutils
-	{
-		let env_bin_dir_str = shell_env
-			.files
-			.bin_dir
-			.to_str()
-			.ok_or(Err::DirToStr)?
-			.to_owned();
-		shell_path = env_bin_dir_str + ":" + &shell_path;
+	if shell.cwd_policy == table::CwdPolicy::Restrict {
+		let mut allowed = cwd.into_iter().collect::<Vec<_>>();
+		for dir in &shell.cwd_allowed {
+			allowed.push(env_table.expand_dir(dir, shell_env, vars)?);
+		}
+
+		let dir = start_dir.as_ref().unwrap_or(&curr_dir);
+		if !allowed.iter().any(|allowed| is_within(dir, allowed)) {
+			return Err(Err::NotAllowed(dir.clone()));
+		}
 	}
-	shell.env("PATH", shell_path);
 
-	let mut shell = shell.spawn().dp()?;
-	shell.wait().dp()?;
+	Ok(start_dir)
+}
 
-	Ok(())
+/// Whether `dir` is `parent` or in it, after resolving symlinks.
+pub(super) fn is_within(dir: &path::Path, parent: &path::Path) -> bool {
+	let canonical =
+		|dir: &path::Path| fs::canonicalize(dir).unwrap_or_else(|_| dir.into());
+	canonical(dir).starts_with(canonical(parent))
 }
@@ -14,6 +14,32 @@ mod cmd_rm;
 pub use cmd_rm::cmd_rm;
 mod cmd_ls;
 pub use cmd_ls::cmd_ls;
+mod cmd_check;
+pub use cmd_check::cmd_check;
+mod cmd_migrate;
+pub use cmd_migrate::cmd_migrate;
+mod cmd_schema;
+pub use cmd_schema::cmd_schema;
+mod cmd_config;
+pub use cmd_config::cmd_config;
+mod cmd_edit;
+pub use cmd_edit::cmd_edit;
+mod cmd_home;
+pub use cmd_home::cmd_home;
+mod cmd_history;
+pub use cmd_history::cmd_history;
+mod cmd_exec;
+pub use cmd_exec::cmd_exec;
+mod cmd_allow;
+pub use cmd_allow::cmd_allow;
+mod cmd_deny;
+pub use cmd_deny::cmd_deny;
+mod cmd_trust;
+pub use cmd_trust::cmd_trust;
+mod cmd_hook;
+pub use cmd_hook::cmd_hook;
+mod cmd_env;
+pub use cmd_env::cmd_env;
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -29,5 +55,31 @@ pub enum Err {
 	#[error(transparent)]
 	Ls(#[from] cmd_ls::Err),
 	#[error(transparent)]
+	Check(#[from] cmd_check::Err),
+	#[error(transparent)]
+	Migrate(#[from] cmd_migrate::Err),
+	#[error(transparent)]
+	Schema(#[from] cmd_schema::Err),
+	#[error(transparent)]
+	Config(#[from] cmd_config::Err),
+	#[error(transparent)]
+	Edit(#[from] cmd_edit::Err),
+	#[error(transparent)]
+	Home(#[from] cmd_home::Err),
+	#[error(transparent)]
+	History(#[from] cmd_history::Err),
+	#[error(transparent)]
+	Exec(#[from] cmd_exec::Err),
+	#[error(transparent)]
+	Allow(#[from] cmd_allow::Err),
+	#[error(transparent)]
+	Deny(#[from] cmd_deny::Err),
+	#[error(transparent)]
+	Trust(#[from] cmd_trust::Err),
+	#[error(transparent)]
+	Hook(#[from] cmd_hook::Err),
+	#[error(transparent)]
+	PrintEnv(#[from] cmd_env::Err),
+	#[error(transparent)]
 	IO(#[from] io::Error),
 }
@@ -38,3 +38,14 @@ macro_rules! dbgfmt {
 	};
 }
 pub(crate) use dbgfmt;
+
+// Like `dbgfmt!` but printing to stderr, for code which also runs for commands
+// whose output is evaluated by the shell, like `cr env` and `cr hook`.
+macro_rules! edbgfmt {
+	($($exs:expr),+) => {
+		if cfg!(debug_assertions) {
+			eprintln!($($exs),+);
+		}
+	};
+}
+pub(crate) use edbgfmt;
@@ -0,0 +1,155 @@
+//! Diagnostics pointing at a location in a config file.
+
+use std::fmt;
+use std::ops;
+use std::path;
+
+use serde::de::{self, Deserialize, Visitor};
+
+/// A message about a span of a config file, rendered with the file path, the
+/// line and column, and the offending line of the source.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diag {
+	pub file: path::PathBuf,
+	/// 1-based line number.
+	pub line: usize,
+	/// 1-based column number, in characters.
+	pub col: usize,
+	/// The source line the span starts on.
+	pub text: String,
+	/// The number of characters to underline on `text`.
+	pub len: usize,
+	pub msg: String,
+}
+
+impl Diag {
+	/// `span` is a byte range into `src`, which is the contents of `file`.
+	pub fn new(
+		file: &path::Path,
+		src: &str,
+		span: Option<ops::Range<usize>>,
+		msg: &str,
+	) -> Self {
+		let span = span.unwrap_or(0..0);
+		let start = span.start.min(src.len());
+		let end = span.end.clamp(start, src.len());
+
+		let line_start = src[..start].rfind('\n').map_or(0, |idx| idx + 1);
+		let line_end =
+			src[start..].find('\n').map_or(src.len(), |idx| start + idx);
+		let text = src[line_start..line_end].trim_end_matches('\r');
+
+		let line = src[..start].matches('\n').count() + 1;
+		let col = src[line_start..start].chars().count() + 1;
+		let len = src[start..end.min(line_end)].chars().count().max(1);
+
+		Self {
+			file: file.to_owned(),
+			line,
+			col,
+			text: text.to_owned(),
+			len,
+			msg: msg.trim_end().to_owned(),
+		}
+	}
+
+	/// `file:line:col`
+	pub fn location(&self) -> String {
+		format!("{}:{}:{}", self.file.display(), self.line, self.col)
+	}
+}
+
+impl fmt::Display for Diag {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		let line = self.line.to_string();
+		let pad = " ".repeat(line.len());
+		let marker_pad: String = self
+			.text
+			.chars()
+			.take(self.col - 1)
+			.map(|ch| if ch == '\t' { '\t' } else { ' ' })
+			.collect();
+
+		writeln!(f, "{}", self.msg)?;
+		writeln!(f, "{pad}--> {}", self.location())?;
+		writeln!(f, "{pad} |")?;
+		writeln!(f, "{line} | {}", self.text)?;
+		write!(f, "{pad} | {marker_pad}{}", "^".repeat(self.len))
+	}
+}
+
+/// Multiple diagnostics, displayed separated by blank lines.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diags(pub Vec<Diag>);
+
+impl fmt::Display for Diags {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		for (idx, diag) in self.0.iter().enumerate() {
+			if idx > 0 {
+				write!(f, "\n\n")?;
+			}
+			write!(f, "{diag}")?;
+		}
+		Ok(())
+	}
+}
+
+/// The most similar string in `candidates` to `name`, if any is similar enough
+/// to be a likely typo.
+pub fn suggest<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+	candidates
+		.iter()
+		.map(|candidate| (strsim::jaro_winkler(name, candidate), *candidate))
+		.filter(|(similarity, _)| *similarity > 0.8)
+		.max_by(|lhs, rhs| lhs.0.total_cmp(&rhs.0))
+		.map(|(_, candidate)| candidate)
+}
+
+/// Names of the fields of the struct `T` as known by serde, including renames.
+pub fn struct_fields<'de, T>() -> &'static [&'static str]
+where
+	T: Deserialize<'de>,
+{
+	let mut fields: &'static [&'static str] = &[];
+	let _ = T::deserialize(FieldsDeserializer {
+		fields: &mut fields,
+	});
+	fields
+}
+
+// Only implements `deserialize_struct` to record the field names and fails on
+// everything else.
+struct FieldsDeserializer<'a> {
+	fields: &'a mut &'static [&'static str],
+}
+
+impl<'de, 'a> de::Deserializer<'de> for FieldsDeserializer<'a> {
+	type Error = de::value::Error;
+
+	fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		Err(de::Error::custom("only structs are supported"))
+	}
+
+	fn deserialize_struct<V>(
+		self,
+		_name: &'static str,
+		fields: &'static [&'static str],
+		visitor: V,
+	) -> Result<V::Value, Self::Error>
+	where
+		V: Visitor<'de>,
+	{
+		*self.fields = fields;
+		self.deserialize_any(visitor)
+	}
+
+	serde::forward_to_deserialize_any! {
+		bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+		bytes byte_buf option unit unit_struct newtype_struct seq tuple
+		tuple_struct map enum identifier ignored_any
+	}
+}
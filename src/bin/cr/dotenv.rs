@@ -0,0 +1,219 @@
+//! Parser for dotenv files.
+//!
+//! Supports the common syntax: `KEY=value` lines with an optional `export`
+//! prefix, `#` comments, single-quoted literal values, double-quoted values
+//! with escapes, and quoted values spanning multiple lines.
+
+use std::fs;
+use std::io;
+use std::path;
+use std::result;
+
+use thiserror::Error;
+
+use crate::diag;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error("Couldn't read dotenv file '{0}': {1}")]
+	Read(path::PathBuf, io::Error),
+	#[error("{0}")]
+	Parse(Box<diag::Diag>),
+}
+
+/// Read and parse the dotenv file `file`, returning the variables in the order
+/// they're defined.
+pub fn read(file: &path::Path) -> Result<Vec<(String, String)>> {
+	let src = fs::read_to_string(file)
+		.map_err(|err| Err::Read(file.to_owned(), err))?;
+	parse(file, &src)
+}
+
+/// Parse `src`, the contents of the dotenv file `file`.
+pub fn parse(file: &path::Path, src: &str) -> Result<Vec<(String, String)>> {
+	Parser { file, src, pos: 0 }.parse()
+}
+
+struct Parser<'a> {
+	file: &'a path::Path,
+	src: &'a str,
+	// Byte offset into `src`.
+	pos: usize,
+}
+
+impl<'a> Parser<'a> {
+	fn parse(mut self) -> Result<Vec<(String, String)>> {
+		let mut vars = Vec::new();
+
+		loop {
+			self.skip(|ch| ch.is_whitespace());
+			match self.peek() {
+				None => return Ok(vars),
+				Some('#') => {
+					self.skip(|ch| ch != '\n');
+					continue;
+				}
+				Some(_) => (),
+			}
+
+			if let Some(rest) = self.rest().strip_prefix("export") {
+				if rest.starts_with([' ', '\t']) {
+					self.pos += "export".len();
+					self.skip_blank();
+				}
+			}
+
+			let key = self.key()?;
+			self.skip_blank();
+			if self.peek() != Some('=') {
+				return Err(self.err(
+					self.pos..self.pos + 1,
+					&format!("Expected '=' after variable name '{key}'"),
+				));
+			}
+			self.pos += 1;
+			self.skip_blank();
+
+			let val = match self.peek() {
+				Some('\'') => self.single_quoted()?,
+				Some('"') => self.double_quoted()?,
+				_ => self.unquoted(),
+			};
+			vars.push((key, val));
+		}
+	}
+
+	fn key(&mut self) -> Result<String> {
+		let start = self.pos;
+		self.skip(|ch| ch.is_ascii_alphanumeric() || ch == '_' || ch == '.');
+		let key = &self.src[start..self.pos];
+
+		if key.is_empty() || key.starts_with(|ch: char| ch.is_ascii_digit()) {
+			let end = self.src[start..]
+				.find(char::is_whitespace)
+				.map_or(self.src.len(), |idx| start + idx);
+			return Err(self.err(start..end, "Expected a variable name"));
+		}
+
+		Ok(key.to_owned())
+	}
+
+	// Until the end of the line or an inline comment.
+	fn unquoted(&mut self) -> String {
+		let start = self.pos;
+		let line = self.rest().split('\n').next().unwrap_or("");
+		let end = line
+			.match_indices('#')
+			.find(|(idx, _)| *idx == 0 || line[..*idx].ends_with([' ', '\t']))
+			.map_or(line.len(), |(idx, _)| idx);
+
+		self.pos += line.len();
+		self.src[start..start + end].trim().to_owned()
+	}
+
+	fn single_quoted(&mut self) -> Result<String> {
+		let start = self.pos;
+		self.pos += 1;
+
+		let Some(len) = self.rest().find('\'') else {
+			return Err(self.err(start..start + 1, "Unterminated single quote"));
+		};
+		let val = self.rest()[..len].to_owned();
+		self.pos += len + 1;
+
+		self.end_of_value()?;
+		Ok(val)
+	}
+
+	fn double_quoted(&mut self) -> Result<String> {
+		let start = self.pos;
+		self.pos += 1;
+
+		let mut val = String::new();
+		let mut chars = self.rest().char_indices();
+		loop {
+			let Some((idx, ch)) = chars.next() else {
+				return Err(
+					self.err(start..start + 1, "Unterminated double quote")
+				);
+			};
+			match ch {
+				'"' => {
+					self.pos += idx + 1;
+					break;
+				}
+				'\\' => match chars.next() {
+					Some((_, 'n')) => val.push('\n'),
+					Some((_, 'r')) => val.push('\r'),
+					Some((_, 't')) => val.push('\t'),
+					Some((_, esc @ ('"' | '\\' | '$' | '`'))) => val.push(esc),
+					// Unknown escapes are kept as is.
+					Some((_, esc)) => {
+						val.push('\\');
+						val.push(esc);
+					}
+					None => val.push('\\'),
+				},
+				ch => val.push(ch),
+			}
+		}
+
+		self.end_of_value()?;
+		Ok(val)
+	}
+
+	// Only blanks and a comment can follow a quoted value on its line.
+	fn end_of_value(&mut self) -> Result<()> {
+		self.skip_blank();
+		match self.peek() {
+			None | Some('\n' | '\r') => Ok(()),
+			Some('#') => {
+				self.skip(|ch| ch != '\n');
+				Ok(())
+			}
+			Some(_) => {
+				let end = self
+					.rest()
+					.find('\n')
+					.map_or(self.src.len(), |idx| self.pos + idx);
+				Err(self
+					.err(self.pos..end, "Unexpected characters after value"))
+			}
+		}
+	}
+
+	fn rest(&self) -> &'a str {
+		&self.src[self.pos..]
+	}
+
+	fn peek(&self) -> Option<char> {
+		self.rest().chars().next()
+	}
+
+	fn skip<F>(&mut self, pred: F)
+	where
+		F: Fn(char) -> bool,
+	{
+		let len = self
+			.rest()
+			.find(|ch| !pred(ch))
+			.unwrap_or(self.rest().len());
+		self.pos += len;
+	}
+
+	fn skip_blank(&mut self) {
+		self.skip(|ch| ch == ' ' || ch == '\t');
+	}
+
+	fn err(&self, span: std::ops::Range<usize>, msg: &str) -> Err {
+		Err::Parse(Box::new(diag::Diag::new(
+			self.file,
+			self.src,
+			Some(span),
+			msg,
+		)))
+	}
+}
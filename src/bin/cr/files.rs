@@ -5,7 +5,10 @@
 //! [XDG Base Directory specification]: https://specifications.freedesktop.org/basedir-spec/basedir-spec-latest.html
 
 use std::env;
+use std::ffi;
+use std::fs;
 use std::io;
+use std::io::Write;
 use std::path;
 use std::result;
 
@@ -37,14 +40,14 @@ pub enum Err {
 	NoExistsBin(path::PathBuf),
 	#[error("Directory '{0}' already exists")]
 	DirExists(path::PathBuf),
+	#[error("Path '{0}' doesn't have a file name")]
+	NoFileName(path::PathBuf),
 }
 
 pub fn lookup_bin(bin: &path::Path) -> Result<path::PathBuf> {
-	let path = env::var("PATH").dp()?;
-	let path = path.split(':');
+	let path = env::var_os("PATH").unwrap_or_default();
 
-	for path_elem in path {
-		let path_elem = path::PathBuf::from(path_elem);
+	for path_elem in env::split_paths(&path) {
 		let bin_in_path_elem = path_elem.join(bin);
 		if let Ok(exists) = bin_in_path_elem.try_exists() {
 			if exists {
@@ -80,3 +83,72 @@ pub fn bin_get_abs(bin: &path::Path) -> Result<path::PathBuf> {
 		lookup_bin(bin)
 	}
 }
+
+/// Write `contents` to a temporary file next to `file` and rename it over
+/// `file` so that `file` is never left partially written. The permissions of
+/// an already existing `file` are kept.
+pub fn write_atomic<C>(file: &path::Path, contents: C) -> Result<()>
+where
+	C: AsRef<[u8]>,
+{
+	let perms = fs::metadata(file).ok().map(|meta| meta.permissions());
+	write_perms(file, contents, perms)
+}
+
+/// Like `write_atomic`, but `file` always gets the permissions `perms`.
+pub fn write_atomic_perms<C>(
+	file: &path::Path,
+	contents: C,
+	perms: fs::Permissions,
+) -> Result<()>
+where
+	C: AsRef<[u8]>,
+{
+	write_perms(file, contents, Some(perms))
+}
+
+fn write_perms<C>(
+	file: &path::Path,
+	contents: C,
+	perms: Option<fs::Permissions>,
+) -> Result<()>
+where
+	C: AsRef<[u8]>,
+{
+	let file_name = file
+		.file_name()
+		.ok_or(Err::NoFileName(file.to_owned()))
+		.dp()?;
+	let mut tmp_name = ffi::OsString::from(".");
+	tmp_name.push(file_name);
+	tmp_name.push(format!(".{}.tmp", std::process::id()));
+	let tmp_file = file.with_file_name(tmp_name);
+
+	let write = || -> io::Result<()> {
+		let mut tmp = fs::File::create(&tmp_file)?;
+		if let Some(perms) = perms {
+			tmp.set_permissions(perms)?;
+		}
+		tmp.write_all(contents.as_ref())?;
+		tmp.sync_all()?;
+		fs::rename(&tmp_file, file)
+	};
+
+	if let Err(err) = write() {
+		let _ = fs::remove_file(&tmp_file);
+		return Err(Err::IO(err)).dp();
+	}
+
+	Ok(())
+}
+
+/// Replace a leading `~` in `path` with `$HOME`, and make relative paths
+/// relative to `base`.
+pub fn resolve(path: &path::Path, base: &path::Path) -> path::PathBuf {
+	if let Ok(rest) = path.strip_prefix("~") {
+		if let Some(home) = env::var_os("HOME") {
+			return path::PathBuf::from(home).join(rest);
+		}
+	}
+	base.join(path)
+}
@@ -0,0 +1,71 @@
+//! Running the commands of `[hooks]` on the host.
+
+use std::collections::HashMap;
+use std::ffi;
+use std::io;
+use std::os::unix::process::ExitStatusExt;
+use std::process;
+use std::result;
+use std::time;
+
+use thiserror::Error;
+
+use crate::run;
+use crate::table;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+
+	#[error("Hook '{0}' command '{1}' couldn't be run: {2}")]
+	Run(&'static str, String, io::Error),
+	#[error("Hook '{0}' command '{1}' failed with {2}")]
+	Failed(&'static str, String, process::ExitStatus),
+	#[error("Hook '{0}' command '{1}' didn't finish within {2:?}")]
+	Timeout(&'static str, String, time::Duration),
+}
+
+/// Run the commands of the hook `stage` in order with `vars` as their only
+/// environment variables. Failed commands are handled by their `on_failure`.
+pub fn run(
+	stage: &'static str,
+	hooks: &[table::Hook],
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+) -> Result<()> {
+	for hook in hooks {
+		let hook = hook.table();
+		let timeout = time::Duration::from_secs(hook.timeout);
+
+		// Commands which couldn't be run are handled like failed ones.
+		let status =
+			run::spawn(run::sh(&hook.cmd).env_clear().envs(vars), timeout)
+				.and_then(|mut child| run::wait_timeout(&mut child, timeout));
+		let err = match status {
+			Ok(Some(status)) if status.success() => continue,
+			Ok(Some(status)) => Err::Failed(stage, hook.cmd, status),
+			Ok(None) => Err::Timeout(stage, hook.cmd, timeout),
+			Err(err) => Err::Run(stage, hook.cmd, err),
+		};
+
+		match hook.on_failure {
+			table::OnFailure::Abort => return Err(err),
+			table::OnFailure::Warn => eprintln!("Warning: {err}"),
+			table::OnFailure::Ignore => (),
+		}
+	}
+
+	Ok(())
+}
+
+/// The exit code of the shell for `CLEANROOM_EXIT_CODE`, `128 + signal` if it
+/// was killed by a signal like shells report it.
+pub fn exit_code(status: process::ExitStatus) -> i32 {
+	status
+		.code()
+		.or_else(|| status.signal().map(|signal| 128 + signal))
+		.unwrap_or(1)
+}
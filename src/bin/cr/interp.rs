@@ -0,0 +1,60 @@
+//! Interpolation of the environment's variables into config values.
+
+use std::collections::HashMap;
+use std::ffi;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::result;
+
+use thiserror::Error;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error("Variable '{0}' isn't set")]
+	NoVar(String),
+	#[error("Unterminated '${{'")]
+	Unterminated,
+}
+
+/// Replace `${VAR}` in `src` with the value of `VAR` in `vars` and `$$` with
+/// `$`, other `$` are kept as is.
+pub fn render(
+	src: &str,
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+) -> Result<Vec<u8>> {
+	let mut out = Vec::with_capacity(src.len());
+	let mut rest = src;
+
+	while let Some(idx) = rest.find('$') {
+		out.extend_from_slice(&rest.as_bytes()[..idx]);
+		rest = &rest[idx + 1..];
+
+		if let Some(after) = rest.strip_prefix('$') {
+			out.push(b'$');
+			rest = after;
+		} else if let Some(after) = rest.strip_prefix('{') {
+			let (var, after) =
+				after.split_once('}').ok_or(Err::Unterminated)?;
+			let val = vars
+				.get(ffi::OsStr::new(var))
+				.ok_or_else(|| Err::NoVar(var.to_owned()))?;
+			out.extend_from_slice(val.as_bytes());
+			rest = after;
+		} else {
+			out.push(b'$');
+		}
+	}
+	out.extend_from_slice(rest.as_bytes());
+
+	Ok(out)
+}
+
+/// Like `render` for values which aren't file contents, e.g. paths.
+pub fn render_os(
+	src: &str,
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+) -> Result<ffi::OsString> {
+	render(src, vars).map(ffi::OsString::from_vec)
+}
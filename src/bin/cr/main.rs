@@ -48,14 +48,25 @@ use thiserror::Error;
 
 #[allow(clippy::module_name_repetitions)]
 pub mod args;
+pub mod check;
 #[allow(clippy::pub_use)]
 pub mod cmds;
 #[allow(clippy::module_name_repetitions)]
 mod debug;
+pub mod diag;
+pub mod dotenv;
 pub mod files;
+pub mod hooks;
+pub mod interp;
 pub mod macros;
+pub mod materialize;
+pub mod migrate;
+pub mod rc;
+pub mod run;
 pub mod senv;
 pub mod table;
+pub mod trust;
+pub mod user;
 
 type Result<T> = result::Result<T, Err>;
 
@@ -107,6 +118,86 @@ fn cr_main() -> Result<()> {
 				return Err(Err::Cmd(cmds::Err::Ls(err)));
 			}
 		}
+
+		args::CmdMainSub::Check { args: args_check } => {
+			if let Err(err) = cmds::cmd_check(&cmd.args, &args_check, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Check(err)));
+			}
+		}
+
+		args::CmdMainSub::Migrate { args: args_migrate } => {
+			if let Err(err) = cmds::cmd_migrate(&cmd.args, &args_migrate, &dirs)
+			{
+				return Err(Err::Cmd(cmds::Err::Migrate(err)));
+			}
+		}
+
+		args::CmdMainSub::Schema { args: args_schema } => {
+			if let Err(err) = cmds::cmd_schema(&cmd.args, &args_schema, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Schema(err)));
+			}
+		}
+
+		args::CmdMainSub::Config { args: args_config } => {
+			if let Err(err) = cmds::cmd_config(&cmd.args, &args_config, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Config(err)));
+			}
+		}
+
+		args::CmdMainSub::Edit { args: args_edit } => {
+			if let Err(err) = cmds::cmd_edit(&cmd.args, &args_edit, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Edit(err)));
+			}
+		}
+
+		args::CmdMainSub::Home { args: args_home } => {
+			if let Err(err) = cmds::cmd_home(&cmd.args, &args_home, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Home(err)));
+			}
+		}
+
+		args::CmdMainSub::History { args: args_history } => {
+			if let Err(err) = cmds::cmd_history(&cmd.args, &args_history, &dirs)
+			{
+				return Err(Err::Cmd(cmds::Err::History(err)));
+			}
+		}
+
+		args::CmdMainSub::Exec { args: args_exec } => {
+			if let Err(err) = cmds::cmd_exec(&cmd.args, &args_exec, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Exec(err)));
+			}
+		}
+
+		args::CmdMainSub::Allow { args: args_allow } => {
+			if let Err(err) = cmds::cmd_allow(&cmd.args, &args_allow, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Allow(err)));
+			}
+		}
+
+		args::CmdMainSub::Deny { args: args_deny } => {
+			if let Err(err) = cmds::cmd_deny(&cmd.args, &args_deny, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Deny(err)));
+			}
+		}
+
+		args::CmdMainSub::Trust { args: args_trust } => {
+			if let Err(err) = cmds::cmd_trust(&cmd.args, &args_trust, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Trust(err)));
+			}
+		}
+
+		args::CmdMainSub::Hook { args: args_hook } => {
+			if let Err(err) = cmds::cmd_hook(&cmd.args, &args_hook, &dirs) {
+				return Err(Err::Cmd(cmds::Err::Hook(err)));
+			}
+		}
+
+		args::CmdMainSub::Env { args: args_env } => {
+			if let Err(err) = cmds::cmd_env(&cmd.args, &args_env, &dirs) {
+				return Err(Err::Cmd(cmds::Err::PrintEnv(err)));
+			}
+		}
 	}
 	Ok(())
 }
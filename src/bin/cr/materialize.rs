@@ -0,0 +1,244 @@
+//! Writing the files of the `[files]` table into an environment.
+//!
+//! The paths of the written files are kept in `files.list` in the
+//! environment's data directory, so that files removed from the table can be
+//! deleted on the next `cr use`. Existing files which aren't in the list are
+//! never replaced.
+
+use std::collections::HashMap;
+use std::env;
+use std::ffi;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::fs::{symlink, PermissionsExt};
+use std::path;
+use std::result;
+
+use thiserror::Error;
+
+use crate::debug::DebugPanic;
+use crate::files;
+use crate::interp;
+use crate::senv;
+use crate::table;
+use crate::user;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	Files(#[from] files::Err),
+
+	#[error("Couldn't read '{1}' for file '{0}': {2}")]
+	Source(path::PathBuf, path::PathBuf, io::Error),
+	#[error(
+		"File '{0}' is relative to the shell's home directory, which is the \
+		 user's, set `shell.home` or make the path absolute"
+	)]
+	HostHome(path::PathBuf),
+	#[error(
+		"Not replacing '{1}' for file '{0}' since it wasn't written by \
+		 cleanroom, remove it first"
+	)]
+	Exists(path::PathBuf, path::PathBuf),
+	#[error("File '{0}' isn't in the environment's data directory '{1}'")]
+	Outside(path::PathBuf, path::PathBuf),
+	#[error("Couldn't render file '{0}': {1}")]
+	Render(path::PathBuf, interp::Err),
+}
+
+const LIST: &str = "files.list";
+/// Mode of the files written from `content` and `template`.
+const RENDERED_MODE: u32 = 0o600;
+
+/// Write the files of `env_table.files`, rendering contents and templates with
+/// `vars`, and delete the ones written by the previous run which were removed
+/// from the table.
+pub fn materialize(
+	env_table: &table::Root,
+	shell_env: &senv::Senv,
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+) -> Result<()> {
+	let list = shell_env.files.data_dir.join(LIST);
+	let prev_written = read_list(&list)?;
+
+	let mut written = Vec::new();
+	if let Err(err) =
+		write_all(env_table, shell_env, vars, &prev_written, &mut written)
+	{
+		// Files written before the error are replaced on the next run.
+		let mut kept = prev_written;
+		for file in written {
+			if !kept.contains(&file) {
+				kept.push(file);
+			}
+		}
+		write_list(&list, &kept)?;
+		return Err(err);
+	}
+
+	for prev in prev_written {
+		if !written.contains(&prev) {
+			remove(&prev)?;
+		}
+	}
+	write_list(&list, &written)?;
+
+	Ok(())
+}
+
+/// Return where `file`, a key of `env_table.files`, is written to.
+pub fn dest(
+	env_table: &table::Root,
+	shell_env: &senv::Senv,
+	file: &path::Path,
+) -> Result<path::PathBuf> {
+	let data_dir = &shell_env.files.data_dir;
+	let outside = || Err::Outside(file.to_owned(), data_dir.clone());
+
+	if file
+		.components()
+		.any(|comp| comp == path::Component::ParentDir)
+	{
+		return Err(outside());
+	}
+
+	if file.is_absolute() {
+		if !file.starts_with(data_dir) {
+			return Err(outside());
+		}
+		return Ok(file.to_owned());
+	}
+
+	// `shell.home` can also be a path to the user's home directory.
+	let home_dir = env_table
+		.home_dir(shell_env)
+		.filter(|home_dir| !is_user_home(home_dir))
+		.ok_or_else(|| Err::HostHome(file.to_owned()))?;
+	Ok(home_dir.join(file))
+}
+
+// Whether `dir` is the home directory of the user from the passwd database or
+// `$HOME`.
+fn is_user_home(dir: &path::Path) -> bool {
+	let Ok(dir) = dir.canonicalize() else {
+		return false;
+	};
+	user::current()
+		.map(|passwd| passwd.home)
+		.into_iter()
+		.chain(env::var_os("HOME").map(Into::into))
+		.any(|home| home.canonicalize().is_ok_and(|home| home == dir))
+}
+
+// Write the files of `env_table.files` in order, adding them to `written`.
+// Existing files are only replaced if they're in `prev_written`.
+fn write_all(
+	env_table: &table::Root,
+	shell_env: &senv::Senv,
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+	prev_written: &[path::PathBuf],
+	written: &mut Vec<path::PathBuf>,
+) -> Result<()> {
+	let cfg_dir = &shell_env.files.cfg_dir;
+	let mut entries: Vec<_> = env_table.files.iter().collect();
+	entries.sort_by(|lhs, rhs| lhs.0.cmp(rhs.0));
+
+	for (file, source) in entries {
+		let dest = dest(env_table, shell_env, file)?;
+		if !prev_written.contains(&dest) && fs::symlink_metadata(&dest).is_ok()
+		{
+			return Err(Err::Exists(file.clone(), dest));
+		}
+		if let Some(parent) = dest.parent() {
+			fs::create_dir_all(parent).dp()?;
+		}
+
+		// Rendered files can contain the values of secrets.
+		match source {
+			table::FileSource::Content(src) => {
+				files::write_atomic_perms(
+					&dest,
+					render(file, src, vars)?,
+					fs::Permissions::from_mode(RENDERED_MODE),
+				)?;
+			}
+			table::FileSource::Template(tmpl) => {
+				let tmpl = files::resolve(tmpl, cfg_dir);
+				let src = fs::read_to_string(&tmpl)
+					.map_err(|err| Err::Source(file.clone(), tmpl, err))?;
+				files::write_atomic_perms(
+					&dest,
+					render(file, &src, vars)?,
+					fs::Permissions::from_mode(RENDERED_MODE),
+				)?;
+			}
+			// Not using `fs::copy` since it would write through a symlink
+			// left by a previous `link`.
+			table::FileSource::Copy(host_file) => {
+				let host_file = files::resolve(host_file, cfg_dir);
+				let source_err =
+					|err| Err::Source(file.clone(), host_file.clone(), err);
+				let perms =
+					fs::metadata(&host_file).map_err(source_err)?.permissions();
+				let contents = fs::read(&host_file).map_err(source_err)?;
+				files::write_atomic_perms(&dest, contents, perms)?;
+			}
+			table::FileSource::Link(host_file) => {
+				let host_file = files::resolve(host_file, cfg_dir);
+				remove(&dest)?;
+				symlink(host_file, &dest).dp()?;
+			}
+		}
+		written.push(dest);
+	}
+
+	Ok(())
+}
+
+fn render(
+	file: &path::Path,
+	src: &str,
+	vars: &HashMap<ffi::OsString, ffi::OsString>,
+) -> Result<Vec<u8>> {
+	interp::render(src, vars).map_err(|err| Err::Render(file.to_owned(), err))
+}
+
+// Remove a file or symlink, directories are left alone.
+fn remove(file: &path::Path) -> Result<()> {
+	match fs::symlink_metadata(file) {
+		Ok(meta) if !meta.is_dir() => fs::remove_file(file).dp()?,
+		Ok(_) => (),
+		Err(err) if err.kind() == io::ErrorKind::NotFound => (),
+		Err(err) => return Err(Err::IO(err)).dp(),
+	}
+	Ok(())
+}
+
+// The paths are separated by NUL since they can contain newlines.
+fn read_list(list: &path::Path) -> Result<Vec<path::PathBuf>> {
+	match fs::read(list) {
+		Ok(contents) => Ok(contents
+			.split(|&byte| byte == 0)
+			.filter(|file| !file.is_empty())
+			.map(|file| ffi::OsString::from_vec(file.to_vec()).into())
+			.collect()),
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+		Err(err) => Err(Err::IO(err)).dp(),
+	}
+}
+
+fn write_list(list: &path::Path, written: &[path::PathBuf]) -> Result<()> {
+	let mut contents = Vec::new();
+	for file in written {
+		contents.extend_from_slice(file.as_os_str().as_bytes());
+		contents.push(0);
+	}
+	files::write_atomic(list, contents)?;
+	Ok(())
+}
@@ -0,0 +1,165 @@
+//! Versioning of the `config.toml` schema and migrations between versions.
+//!
+//! Migrations operate on the TOML document instead of `table::Root` so that
+//! comments and formatting survive the upgrade.
+
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path;
+use std::result;
+
+use thiserror::Error;
+
+use crate::debug::DebugPanic;
+use crate::files;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	Toml(#[from] toml_edit::TomlError),
+	#[error(transparent)]
+	Files(#[from] files::Err),
+
+	#[error("Key `version` must be a non-negative integer")]
+	InvalidVersion,
+	#[error(
+		"Config version {0} is newer than the latest version {VERSION} \
+		 supported by this version of cleanroom"
+	)]
+	Newer(u32),
+}
+
+struct Migration {
+	desc: &'static str,
+	apply: fn(&mut toml_edit::DocumentMut),
+}
+
+/// `MIGRATIONS[n]` upgrades a config from version `n` to version `n + 1`.
+const MIGRATIONS: &[Migration] = &[Migration {
+	desc: "Add the `version` key",
+	apply: v0_to_v1,
+}];
+
+/// The version written to new configs and which older configs are migrated
+/// to.
+#[allow(clippy::cast_possible_truncation)]
+pub const VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// The result of migrating a config.
+#[non_exhaustive]
+#[derive(Debug)]
+pub struct Migrated {
+	pub from: u32,
+	pub to: u32,
+	/// Descriptions of the applied migrations.
+	pub steps: Vec<&'static str>,
+	/// The migrated config.
+	pub src: String,
+	/// The copy of the original config, if it was written.
+	pub backup: Option<path::PathBuf>,
+}
+
+/// The version of a config, configs without a version are version 0.
+pub fn version_of(doc: &toml_edit::DocumentMut) -> Result<u32> {
+	match doc.get("version") {
+		None => Ok(0),
+		Some(item) => item
+			.as_integer()
+			.and_then(|version| u32::try_from(version).ok())
+			.ok_or(Err::InvalidVersion),
+	}
+}
+
+/// Migrate the contents of a config to `VERSION`, returns `None` if it's
+/// already up to date.
+pub fn migrate_str(src: &str) -> Result<Option<Migrated>> {
+	let mut doc: toml_edit::DocumentMut = src.parse()?;
+	let from = version_of(&doc)?;
+
+	if from > VERSION {
+		return Err(Err::Newer(from));
+	}
+	if from == VERSION {
+		return Ok(None);
+	}
+
+	let mut steps = Vec::new();
+	for version in from..VERSION {
+		let migration = &MIGRATIONS[version as usize];
+		(migration.apply)(&mut doc);
+		doc["version"] = toml_edit::value(i64::from(version + 1));
+		steps.push(migration.desc);
+	}
+
+	Ok(Some(Migrated {
+		from,
+		to: VERSION,
+		steps,
+		src: doc.to_string(),
+		backup: None,
+	}))
+}
+
+/// Migrate a config file in place, keeping a copy of the original next to it
+/// as `<file>.v<version>.bak`, or `<file>.v<version>.<n>.bak` if that already
+/// exists. Nothing is written if `dry_run` is true.
+pub fn migrate_file(
+	file: &path::Path,
+	dry_run: bool,
+) -> Result<Option<Migrated>> {
+	let src = fs::read_to_string(file).dp()?;
+	let Some(mut migrated) = migrate_str(&src)? else {
+		return Ok(None);
+	};
+
+	if !dry_run {
+		let backup = write_backup(file, &src, migrated.from)?;
+		files::write_atomic(file, &migrated.src).dp()?;
+		migrated.backup = Some(backup);
+	}
+
+	Ok(Some(migrated))
+}
+
+// Writes `src` to the first backup path which doesn't exist yet, so that the
+// backups of earlier migrations are never overwritten.
+fn write_backup(
+	file: &path::Path,
+	src: &str,
+	version: u32,
+) -> Result<path::PathBuf> {
+	for n in 0.. {
+		let mut backup = file.as_os_str().to_owned();
+		if n == 0 {
+			backup.push(format!(".v{version}.bak"));
+		} else {
+			backup.push(format!(".v{version}.{n}.bak"));
+		}
+		let backup = path::PathBuf::from(backup);
+
+		match fs::OpenOptions::new()
+			.write(true)
+			.create_new(true)
+			.open(&backup)
+		{
+			Ok(mut out) => {
+				out.write_all(src.as_bytes()).dp()?;
+				return Ok(backup);
+			}
+			Err(err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+			Err(err) => return Err(Err::IO(err)).dp(),
+		}
+	}
+
+	unreachable!("ran out of backup paths")
+}
+
+// Configs before version 1 only lacked the `version` key, which is added for
+// every migration.
+fn v0_to_v1(_doc: &mut toml_edit::DocumentMut) {}
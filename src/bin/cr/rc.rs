@@ -0,0 +1,250 @@
+//! Generating the rc file of an environment's shell from `shell.rc`.
+
+use std::env;
+use std::ffi;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path;
+use std::result;
+
+use thiserror::Error;
+
+use crate::debug::DebugPanic;
+use crate::files;
+use crate::senv;
+use crate::table;
+use crate::table::Flavor;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	Files(#[from] files::Err),
+}
+
+/// Contents of the hand-written rc file created by `cr new`.
+pub const SCAFFOLD: &str = "\
+# Sourced by the rc file cleanroom generates from `[shell.rc]` in config.toml
+# every time the environment is used, after the aliases and functions defined
+# there.
+";
+
+/// Path of the generated rc file for `flavor`.
+pub fn path(flavor: Flavor, shell_env: &senv::Senv) -> path::PathBuf {
+	let name = match flavor {
+		Flavor::Sh => "rc.sh",
+		Flavor::Bash => "rc.bash",
+		// Read from `ZDOTDIR`, so the name is fixed.
+		Flavor::Zsh => ".zshrc",
+		Flavor::Fish => "rc.fish",
+	};
+	shell_env.files.rc_dir.join(name)
+}
+
+/// Generate the rc file for the environment's shell and write it to the
+/// environment's data directory.
+pub fn write(env_table: &table::Root, shell_env: &senv::Senv) -> Result<()> {
+	let shell = &env_table.shell;
+	let flavor = shell.flavor();
+	let user_rc = match flavor {
+		Flavor::Fish => shell_env.files.rc_file.with_extension("fish"),
+		Flavor::Sh | Flavor::Bash | Flavor::Zsh => {
+			shell_env.files.rc_file.clone()
+		}
+	};
+	let mut rc =
+		generate(&shell.rc, flavor, &shell_env.files.cfg_dir, &user_rc);
+	rc.push(post_enter(flavor, &env_table.hooks.post_enter));
+
+	fs::create_dir_all(&shell_env.files.rc_dir).dp()?;
+	files::write_atomic(&path(flavor, shell_env), rc.as_bytes())?;
+
+	Ok(())
+}
+
+/// Generate the rc file in the syntax of `flavor`, sourcing `user_rc` at the
+/// end if it exists when the shell starts.
+pub fn generate(
+	rc: &table::Rc,
+	flavor: Flavor,
+	cfg_dir: &path::Path,
+	user_rc: &path::Path,
+) -> ffi::OsString {
+	let mut out = ffi::OsString::from(
+		"# Generated by cleanroom from `[shell.rc]`, changes are overwritten.\n",
+	);
+
+	for file in &rc.source {
+		let file = files::resolve(file, cfg_dir);
+		out.push(source(flavor, file.as_os_str()));
+	}
+
+	let mut functions: Vec<_> = rc.functions.iter().collect();
+	functions.sort();
+	for (name, body) in functions {
+		let body = body.trim_end();
+		match flavor {
+			Flavor::Fish => out.push(format!("function {name}\n{body}\nend\n")),
+			Flavor::Sh | Flavor::Bash | Flavor::Zsh => {
+				out.push(format!("{name}() {{\n{body}\n}}\n"));
+			}
+		}
+	}
+
+	let mut aliases: Vec<_> = rc.aliases.iter().collect();
+	aliases.sort();
+	for (name, cmd) in aliases {
+		let cmd = quote(flavor, ffi::OsStr::new(cmd));
+		match flavor {
+			Flavor::Fish => out.push(format!("alias {name} ")),
+			Flavor::Sh | Flavor::Bash | Flavor::Zsh => {
+				out.push(format!("alias {name}="));
+			}
+		}
+		out.push(cmd);
+		out.push("\n");
+	}
+
+	for snippet in &rc.snippets {
+		out.push(snippet.trim_end());
+		out.push("\n");
+	}
+
+	out.push(source_if_exists(flavor, user_rc.as_os_str()));
+	out
+}
+
+/// The commands of `hooks.post_enter` with their failure policies, which
+/// don't have timeouts since they run in the shell.
+pub fn post_enter(flavor: Flavor, hooks: &[table::Hook]) -> String {
+	let mut out = String::new();
+
+	for hook in hooks {
+		let hook = hook.table();
+		let warning =
+			format!("cleanroom: post_enter hook '{}' failed", hook.cmd);
+		let warning = quote(flavor, ffi::OsStr::new(&warning));
+		let warning = warning.to_string_lossy();
+		let cmd = hook.cmd.trim_end();
+
+		let on_failure = match (flavor, hook.on_failure) {
+			(_, table::OnFailure::Ignore) => String::from("true"),
+			(Flavor::Fish, table::OnFailure::Abort) => {
+				String::from("exit $status")
+			}
+			(_, table::OnFailure::Abort) => String::from("exit $?"),
+			(_, table::OnFailure::Warn) => format!("echo {warning} >&2"),
+		};
+		match flavor {
+			Flavor::Fish => {
+				out.push_str(&format!(
+					"begin
+{cmd}
+end; or {on_failure}
+"
+				));
+			}
+			Flavor::Sh | Flavor::Bash | Flavor::Zsh => {
+				out.push_str(&format!(
+					"{{
+{cmd}
+}} || {on_failure}
+"
+				));
+			}
+		}
+	}
+
+	out
+}
+
+/// Quote `val` as a single word for `flavor`.
+pub fn quote(flavor: Flavor, val: &ffi::OsStr) -> ffi::OsString {
+	let mut quoted = vec![b'\''];
+	for &byte in val.as_bytes() {
+		match (flavor, byte) {
+			(Flavor::Fish, b'\'' | b'\\') => quoted.extend([b'\\', byte]),
+			(Flavor::Sh | Flavor::Bash | Flavor::Zsh, b'\'') => {
+				quoted.extend(b"'\\''");
+			}
+			_ => quoted.push(byte),
+		}
+	}
+	quoted.push(b'\'');
+	ffi::OsString::from_vec(quoted)
+}
+
+/// A command which exports `var` with the value `val` in the syntax of
+/// `flavor`, or unsets it if `val` is `None`.
+pub fn set_var(
+	flavor: Flavor,
+	var: &ffi::OsStr,
+	val: Option<&ffi::OsStr>,
+) -> Vec<u8> {
+	let var = var.as_bytes();
+	let mut line = Vec::new();
+	match (flavor, val) {
+		(Flavor::Fish, Some(val)) => {
+			line.extend_from_slice(b"set -gx ");
+			line.extend_from_slice(var);
+			// Fish keeps `*PATH` variables as lists.
+			let elems: Vec<_> = if var.ends_with(b"PATH") {
+				env::split_paths(val)
+					.map(path::PathBuf::into_os_string)
+					.collect()
+			} else {
+				vec![val.to_owned()]
+			};
+			for elem in elems {
+				line.push(b' ');
+				line.extend_from_slice(quote(flavor, &elem).as_bytes());
+			}
+		}
+		(Flavor::Fish, None) => {
+			line.extend_from_slice(b"set -e ");
+			line.extend_from_slice(var);
+		}
+		(Flavor::Sh | Flavor::Bash | Flavor::Zsh, Some(val)) => {
+			line.extend_from_slice(b"export ");
+			line.extend_from_slice(var);
+			line.push(b'=');
+			line.extend_from_slice(quote(flavor, val).as_bytes());
+		}
+		(Flavor::Sh | Flavor::Bash | Flavor::Zsh, None) => {
+			line.extend_from_slice(b"unset ");
+			line.extend_from_slice(var);
+		}
+	}
+	line
+}
+
+fn source(flavor: Flavor, file: &ffi::OsStr) -> ffi::OsString {
+	let mut line = ffi::OsString::from(match flavor {
+		Flavor::Fish => "source ",
+		Flavor::Sh | Flavor::Bash | Flavor::Zsh => ". ",
+	});
+	line.push(quote(flavor, file));
+	line.push("\n");
+	line
+}
+
+fn source_if_exists(flavor: Flavor, file: &ffi::OsStr) -> ffi::OsString {
+	let file = quote(flavor, file);
+	let mut line = ffi::OsString::from(match flavor {
+		Flavor::Fish => "test -r ",
+		Flavor::Sh | Flavor::Bash | Flavor::Zsh => "[ -r ",
+	});
+	line.push(&file);
+	line.push(match flavor {
+		Flavor::Fish => "; and source ",
+		Flavor::Sh | Flavor::Bash | Flavor::Zsh => " ] && . ",
+	});
+	line.push(&file);
+	line.push("\n");
+	line
+}
@@ -0,0 +1,150 @@
+//! Running commands on the host with timeouts.
+
+use std::io;
+use std::io::Read;
+use std::os::unix::process::CommandExt;
+use std::process;
+use std::result;
+use std::thread;
+use std::time;
+
+use thiserror::Error;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+
+	#[error("Command '{0}' didn't finish within {1:?}")]
+	Timeout(String, time::Duration),
+}
+
+// How often to check whether the child exited.
+const POLL: time::Duration = time::Duration::from_millis(10);
+
+/// A command run with `/bin/sh -c`.
+pub fn sh(cmd: &str) -> process::Command {
+	let mut sh = process::Command::new("/bin/sh");
+	sh.arg("-c").arg(cmd);
+	sh
+}
+
+/// Spawn `cmd` to be waited for with `wait_timeout`. Unless `timeout` is
+/// zero it runs in its own process group, so that the processes it starts are
+/// killed with it. If cleanroom is in the foreground of the terminal on stdin
+/// the group is made the foreground one, so that the command can still prompt.
+pub fn spawn(
+	cmd: &mut process::Command,
+	timeout: time::Duration,
+) -> io::Result<process::Child> {
+	if !timeout.is_zero() {
+		cmd.process_group(0);
+		if is_foreground() {
+			// SAFETY: `set_foreground` and `getpid` are async-signal-safe.
+			unsafe {
+				cmd.pre_exec(|| set_foreground(libc::getpid()));
+			}
+		}
+	}
+	cmd.spawn()
+}
+
+/// Wait for `child` started by `spawn` to exit, killing its process group if
+/// it takes longer than `timeout`. A zero `timeout` waits indefinitely.
+pub fn wait_timeout(
+	child: &mut process::Child,
+	timeout: time::Duration,
+) -> io::Result<Option<process::ExitStatus>> {
+	if timeout.is_zero() {
+		return child.wait().map(Some);
+	}
+
+	let pgid = libc::pid_t::try_from(child.id()).map_err(io::Error::other)?;
+	let start = time::Instant::now();
+	let status = loop {
+		if let Some(status) = child.try_wait()? {
+			break Some(status);
+		}
+		if start.elapsed() >= timeout {
+			// SAFETY: `kill` has no memory safety requirements, and the group
+			// can't have been reused since the child wasn't reaped yet.
+			if unsafe { libc::kill(-pgid, libc::SIGKILL) } != 0 {
+				return Err(io::Error::last_os_error());
+			}
+			child.wait()?;
+			break None;
+		}
+		thread::sleep(POLL);
+	};
+
+	// Take back the terminal given to the child by `spawn`.
+	// SAFETY: These functions have no memory safety requirements.
+	if unsafe { libc::tcgetpgrp(libc::STDIN_FILENO) } == pgid {
+		set_foreground(unsafe { libc::getpgrp() })?;
+	}
+
+	Ok(status)
+}
+
+// Make `pgid` the foreground process group of the terminal on stdin. SIGTTOU
+// is ignored meanwhile since it's sent to callers in a background group. Only
+// calls async-signal-safe functions, so it can be used in `pre_exec`.
+fn set_foreground(pgid: libc::pid_t) -> io::Result<()> {
+	// SAFETY: These functions have no memory safety requirements.
+	unsafe {
+		let prev = libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+		let res = libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+		let err = io::Error::last_os_error();
+		libc::signal(libc::SIGTTOU, prev);
+		if res != 0 {
+			return Err(err);
+		}
+	}
+	Ok(())
+}
+
+// Whether stdin is a terminal which has the process group of cleanroom in the
+// foreground.
+fn is_foreground() -> bool {
+	// SAFETY: These functions have no memory safety requirements.
+	unsafe {
+		libc::isatty(libc::STDIN_FILENO) == 1
+			&& libc::tcgetpgrp(libc::STDIN_FILENO) == libc::getpgrp()
+	}
+}
+
+/// Run `cmd` capturing its stdout, stdin and stderr are inherited so the
+/// command can prompt the user. `name` is only used for the error message.
+pub fn output_timeout(
+	name: &str,
+	cmd: &mut process::Command,
+	timeout: time::Duration,
+) -> Result<(process::ExitStatus, Vec<u8>)> {
+	let mut child = spawn(cmd.stdout(process::Stdio::piped()), timeout)?;
+
+	// Read on another thread so a full pipe doesn't block the child.
+	let mut stdout = child.stdout.take();
+	let reader = thread::spawn(move || -> io::Result<Vec<u8>> {
+		let mut buf = Vec::new();
+		if let Some(stdout) = stdout.as_mut() {
+			stdout.read_to_end(&mut buf)?;
+		}
+		Ok(buf)
+	});
+
+	// The whole process group is killed on a timeout, which closes the pipe
+	// so the reader can be joined either way.
+	let status = wait_timeout(&mut child, timeout)?;
+	let stdout = reader
+		.join()
+		.unwrap_or_else(|_| Err(io::Error::other("stdout reader panicked")));
+	let Some(status) = status else {
+		return Err(Err::Timeout(name.to_owned(), timeout));
+	};
+	let stdout = stdout?;
+
+	Ok((status, stdout))
+}
@@ -1,15 +1,22 @@
 //! Structs and methods for storing and operating on files related to the shell
 //! environments.
 
+use std::env;
+use std::ffi;
 use std::fs;
 use std::io;
+use std::os::unix::ffi::OsStrExt;
 use std::path;
+use std::process;
 use std::result;
+use std::time;
 
+use sha2::Digest;
 use thiserror::Error;
 use toml::ser;
 
-use crate::debug::{dbgfmt, DebugPanic};
+use crate::debug::{edbgfmt, DebugPanic};
+use crate::rc;
 use crate::table;
 
 type Result<T> = result::Result<T, Err>;
@@ -26,14 +33,41 @@ pub enum Err {
 	MissingDir(String, path::PathBuf),
 	#[error("File '{1}' doesn't exist for environment '{0}'")]
 	MissingFile(String, path::PathBuf),
+	#[error(
+		"Didn't find a `.cleanroom.toml` or `.cleanroom/config.toml` in '{0}' \
+		 or its parents"
+	)]
+	NoProject(path::PathBuf),
+	#[error(
+		"Invalid environment name '{0}', names can't be empty, `.`, `..` or \
+		 contain `/`"
+	)]
+	InvalidName(String),
 }
 
 use std::cmp::{Eq, Ord, PartialEq, PartialOrd};
 
+/// Config file of a project-local environment.
+pub const PROJECT_FILE: &str = ".cleanroom.toml";
+/// Directory with the config file of a project-local environment, used if the
+/// project has more files for cleanroom.
+pub const PROJECT_DIR: &str = ".cleanroom";
+// Directory in the data home with the data directories of project-local
+// environments, named after the hash of the project's path.
+const PROJECTS_DIR: &str = ".projects";
+
+/// Variable with the name of the environment the process is in.
+pub const ENV_VAR: &str = "CLEANROOM_ENV";
+/// Variable with the number of environments the process is nested in.
+pub const DEPTH_VAR: &str = "CLEANROOM_DEPTH";
+
 #[non_exhaustive]
 #[derive(Eq, Ord, PartialEq, PartialOrd, Debug)]
 pub struct Senv {
 	pub name: String,
+	/// Whether it's a project-local environment, whose config comes from the
+	/// project and has to be allowed with `cr allow`.
+	pub project: bool,
 	pub files: Files,
 }
 
@@ -44,25 +78,136 @@ pub struct Files {
 	pub cfg_file: path::PathBuf,
 	pub data_dir: path::PathBuf,
 	pub bin_dir: path::PathBuf,
+	/// Home directory of the shell if `shell.home` is "private".
+	pub home_dir: path::PathBuf,
+	/// History file of the shell if `shell.history` is "isolated".
+	pub history_file: path::PathBuf,
+	/// Directory of the rc file generated from `shell.rc`.
+	pub rc_dir: path::PathBuf,
+	/// Hand-written rc file sourced by the generated one, `rc.fish` next to it
+	/// is used for fish.
+	pub rc_file: path::PathBuf,
 }
 
 impl Senv {
 	pub fn new_xdg(name: &str, dirs: &xdg::BaseDirectories) -> Result<Self> {
-		let name = String::from(name);
-		let cfg_dir = dirs.get_config_home().join(&name);
+		// The name is joined to the config and data homes, so it must not
+		// point outside of them.
+		if name.is_empty() || name == "." || name == ".." || name.contains('/')
+		{
+			return Err(Err::InvalidName(name.to_owned()));
+		}
+
+		let cfg_dir = dirs.get_config_home().join(name);
 		let cfg_file = cfg_dir.join("config.toml");
-		let data_dir = dirs.get_data_home().join(&name);
-		let bin_dir = data_dir.join("bin");
+		let rc_file = cfg_dir.join("rc.sh");
+		let data_dir = dirs.get_data_home().join(name);
+
+		Ok(Self::with_files(name, false, cfg_file, rc_file, data_dir))
+	}
+
+	/// The environment `name` refers to, a project-local environment if it's
+	/// `.` or contains a `/` and a named one otherwise.
+	pub fn from_name(name: &str, dirs: &xdg::BaseDirectories) -> Result<Self> {
+		if name == "." || name.contains('/') {
+			Self::find_project(path::Path::new(name), dirs)
+		} else {
+			Self::new_xdg(name, dirs)
+		}
+	}
+
+	/// The project-local environment of `dir` or the closest of its parents
+	/// which has a `.cleanroom.toml` or `.cleanroom/config.toml`. Nothing is
+	/// written, see `register`.
+	pub fn find_project(
+		dir: &path::Path,
+		dirs: &xdg::BaseDirectories,
+	) -> Result<Self> {
+		let dir = path::absolute(dir).dp()?;
+
+		for project_dir in dir.ancestors() {
+			for cfg_file in [
+				project_dir.join(PROJECT_FILE),
+				project_dir.join(PROJECT_DIR).join("config.toml"),
+			] {
+				if !cfg_file.is_file() {
+					continue;
+				}
 
-		Ok(Self {
-			name,
+				return Self::new_project(&cfg_file, dirs);
+			}
+		}
+
+		Err(Err::NoProject(dir))
+	}
+
+	/// The project-local environment with the config file `cfg_file`, named
+	/// after the project's directory.
+	pub fn new_project(
+		cfg_file: &path::Path,
+		dirs: &xdg::BaseDirectories,
+	) -> Result<Self> {
+		let cfg_file = fs::canonicalize(cfg_file).dp()?;
+		let cfg_dir = cfg_file.parent().unwrap_or(path::Path::new("/"));
+		let (project_dir, rc_file) = if cfg_dir.ends_with(PROJECT_DIR) {
+			(cfg_dir.parent().unwrap_or(cfg_dir), cfg_dir.join("rc.sh"))
+		} else {
+			(cfg_dir, cfg_dir.join(".cleanroom.rc.sh"))
+		};
+
+		let hash = sha2::Sha256::digest(project_dir.as_os_str().as_bytes());
+		let hash: String =
+			hash[..8].iter().map(|byte| format!("{byte:02x}")).collect();
+		let data_dir = dirs.get_data_home().join(PROJECTS_DIR).join(hash);
+
+		Ok(Self::with_files(
+			&project_dir.to_string_lossy(),
+			true,
+			cfg_file.clone(),
+			rc_file,
+			data_dir,
+		))
+	}
+
+	/// Create the data directory of a project-local environment, with a file
+	/// pointing back to its config so that it's listed by `cr ls`.
+	pub fn register(&self) -> Result<()> {
+		if !self.project {
+			return Ok(());
+		}
+
+		fs::create_dir_all(&self.files.data_dir).dp()?;
+		let project_file = self.files.data_dir.join("project");
+		if !project_file.exists() {
+			fs::write(project_file, self.files.cfg_file.as_os_str().as_bytes())
+				.dp()?;
+		}
+		Ok(())
+	}
+
+	fn with_files(
+		name: &str,
+		project: bool,
+		cfg_file: path::PathBuf,
+		rc_file: path::PathBuf,
+		data_dir: path::PathBuf,
+	) -> Self {
+		let cfg_dir = cfg_file.parent().unwrap_or(path::Path::new("/")).into();
+
+		Self {
+			name: String::from(name),
+			project,
 			files: Files {
 				cfg_dir,
 				cfg_file,
+				bin_dir: data_dir.join("bin"),
+				home_dir: data_dir.join("home"),
+				history_file: data_dir.join("history"),
+				rc_dir: data_dir.join("rc"),
+				rc_file,
 				data_dir,
-				bin_dir,
 			},
-		})
+		}
 	}
 
 	pub fn create_xdg(self) -> Result<Self> {
@@ -70,9 +215,10 @@ impl Senv {
 		fs::create_dir_all(&self.files.data_dir).dp()?;
 		fs::create_dir_all(&self.files.bin_dir).dp()?;
 		fs::File::create_new(&self.files.cfg_file).dp()?;
+		fs::write(&self.files.rc_file, rc::SCAFFOLD).dp()?;
 		fs::write(
 			&self.files.cfg_file,
-			toml::to_string_pretty(&table::Root::new()).dp()?,
+			toml::to_string_pretty(&table::Root::new_env()).dp()?,
 		)
 		.dp()?;
 		Ok(self)
@@ -83,10 +229,33 @@ impl Senv {
 		dirs: &xdg::BaseDirectories,
 	) -> Result<Self> {
 		println!("{:?}", dirs);
-		Self::new_xdg(name, dirs).dp()?.create_xdg().dp()
+		Self::new_xdg(name, dirs)?.create_xdg().dp()
+	}
+
+	/// Variables which tell programs in the environment which environment
+	/// they're in and where its files are.
+	pub fn cleanroom_vars(&self) -> Vec<(&'static str, ffi::OsString)> {
+		vec![
+			(ENV_VAR, self.name.clone().into()),
+			("CLEANROOM_CONFIG_DIR", self.files.cfg_dir.clone().into()),
+			("CLEANROOM_DATA_DIR", self.files.data_dir.clone().into()),
+			("CLEANROOM_BIN_DIR", self.files.bin_dir.clone().into()),
+			(DEPTH_VAR, (depth() + 1).to_string().into()),
+			("CLEANROOM_SESSION", session_id().into()),
+		]
 	}
 
+	/// Remove the environment's config and data directories. Only the data
+	/// directory of project-local environments is removed, their config
+	/// belongs to the project.
 	pub fn rm(self) -> Result<()> {
+		if self.project {
+			if self.files.data_dir.try_exists().dp()? {
+				fs::remove_dir_all(&self.files.data_dir).dp()?;
+			}
+			return Ok(());
+		}
+
 		fs::remove_dir_all(&self.files.cfg_dir).dp()?;
 		fs::remove_dir_all(&self.files.data_dir).dp()?;
 
@@ -110,7 +279,9 @@ impl Senv {
 			.dp();
 		}
 
-		if !self.files.data_dir.try_exists().dp()? {
+		// Project-local environments only get a data directory once they're
+		// used.
+		if !self.project && !self.files.data_dir.try_exists().dp()? {
 			return Err(Err::MissingDir(
 				self.name.clone(),
 				self.files.data_dir.clone(),
@@ -121,25 +292,39 @@ impl Senv {
 		Ok(())
 	}
 
+	pub fn get_vec(dirs: &xdg::BaseDirectories) -> Result<Vec<Self>> {
+		let mut shell_envs = Self::get_vec_unchecked(dirs)?;
+		shell_envs.retain(|shell_env| shell_env.is_valid().is_ok());
+		Ok(shell_envs)
+	}
+
+	/// Like `get_vec` but also returns the directories which aren't valid
+	/// environments.
 	#[allow(
 		clippy::missing_panics_doc,
 		clippy::unwrap_in_result,
 		clippy::unwrap_used
 	)]
-	pub fn get_vec(dirs: &xdg::BaseDirectories) -> Result<Vec<Self>> {
+	pub fn get_vec_unchecked(dirs: &xdg::BaseDirectories) -> Result<Vec<Self>> {
 		let mut shell_envs: Vec<Self> = Vec::new();
 
-		let files = fs::read_dir(dirs.get_config_home()).dp()?;
+		let cfg_home = dirs.get_config_home();
+		// Only project-local environments were used so far.
+		if !cfg_home.try_exists().dp()? {
+			return Ok(Self::get_vec_projects(dirs));
+		}
+
+		let files = fs::read_dir(cfg_home).dp()?;
 		for file in files {
 			if let Err(err) = file {
-				dbgfmt!("{}:{} {}", file!(), line!(), err);
+				edbgfmt!("{}:{} {}", file!(), line!(), err);
 				continue;
 			}
 			let file = file.unwrap();
 
 			let meta = file.metadata();
 			if let Err(err) = meta {
-				dbgfmt!("{}:{} {}", file!(), line!(), err);
+				edbgfmt!("{}:{} {}", file!(), line!(), err);
 				continue;
 			}
 			let meta = meta.unwrap();
@@ -151,7 +336,7 @@ impl Senv {
 			let file_name = file.file_name();
 			let file_name = file_name.to_str();
 			if file_name.is_none() {
-				dbgfmt!(
+				edbgfmt!(
 					"{}:{} {}",
 					file!(),
 					line!(),
@@ -167,12 +352,49 @@ impl Senv {
 			}
 			let shell_env = shell_env.unwrap();
 
-			if shell_env.is_valid().is_err() {
-				continue;
-			}
-
 			shell_envs.push(shell_env);
 		}
+
+		shell_envs.extend(Self::get_vec_projects(dirs));
 		Ok(shell_envs)
 	}
+
+	// Project-local environments which were used before and still have their
+	// config file.
+	fn get_vec_projects(dirs: &xdg::BaseDirectories) -> Vec<Self> {
+		let projects_dir = dirs.get_data_home().join(PROJECTS_DIR);
+		let Ok(data_dirs) = fs::read_dir(projects_dir) else {
+			return Vec::new();
+		};
+
+		data_dirs
+			.filter_map(|data_dir| {
+				let project_file = data_dir.ok()?.path().join("project");
+				let cfg_file = fs::read(project_file).ok()?;
+				let cfg_file =
+					path::Path::new(ffi::OsStr::from_bytes(&cfg_file));
+				if !cfg_file.is_file() {
+					return None;
+				}
+				Self::new_project(cfg_file, dirs).ok()
+			})
+			.collect()
+	}
+}
+
+/// Number of environments the current process is nested in, 0 if it isn't in
+/// an environment.
+pub fn depth() -> u32 {
+	env::var(DEPTH_VAR)
+		.ok()
+		.and_then(|depth| depth.parse().ok())
+		.unwrap_or(0)
+}
+
+// Unique for every `cr use` on the host, made of the PID and the start time.
+fn session_id() -> String {
+	let nanos = time::SystemTime::now()
+		.duration_since(time::UNIX_EPOCH)
+		.map_or(0, |since| since.as_nanos());
+	format!("{:x}-{nanos:x}", process::id())
 }
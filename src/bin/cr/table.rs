@@ -2,18 +2,29 @@
 
 use std::cmp::PartialEq;
 use std::collections::HashMap;
-use std::{cmp, env, ffi, fs, io, os, path, result};
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::{cmp, env, ffi, fs, io, ops, os, path, process, result, time};
 
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use toml::de;
 
-use crate::debug::{dbgfmt, DebugPanic};
+use crate::debug::{edbgfmt, DebugPanic};
+use crate::diag;
+use crate::dotenv;
 use crate::files;
+use crate::interp;
 use crate::macros::pathbuf;
+use crate::migrate;
+use crate::rc;
+use crate::run;
 use crate::senv;
+use crate::user;
 
-type Result<T> = result::Result<T, Err>;
+// `E` since the code derived by `JsonSchema` for `#[serde(with = "...")]`
+// fields names `Result` with two arguments.
+type Result<T, E = Err> = result::Result<T, E>;
 
 #[non_exhaustive]
 #[derive(Debug, Error)]
@@ -24,12 +35,37 @@ pub enum Err {
 	Files(#[from] files::Err),
 	#[error(transparent)]
 	TomlDeserialize(#[from] de::Error),
+	#[error("{0}")]
+	Parse(Box<diag::Diag>),
+	#[error("{0}")]
+	UnknownKeys(diag::Diags),
+	#[error(transparent)]
+	Migrate(#[from] migrate::Err),
 	#[error("Didn't find environment variable '{0}' in parent")]
 	EnvVarNotPresent(String),
-	#[error("Environment variable doesn't contain valid unicode: ''")]
-	EnvVarNotUnicode(ffi::OsString),
+	#[error(
+		"Didn't find required environment variables in parent:{}",
+		fmt_missing(.0)
+	)]
+	EnvVarsNotPresent(Vec<(String, InheritTable)>),
 	#[error(transparent)]
 	ShellEnv(#[from] senv::Err),
+	#[error(transparent)]
+	Run(#[from] run::Err),
+	#[error(transparent)]
+	Dotenv(#[from] dotenv::Err),
+	#[error("Couldn't expand '{0}': {1}")]
+	Interp(String, interp::Err),
+
+	#[error("Couldn't read file '{1}' for variable '{0}': {2}")]
+	SecretFile(String, path::PathBuf, io::Error),
+	#[error(
+		"Not reading file '{1}' for variable '{0}', it's readable by group or \
+		 others (mode {2:o}), run `chmod go-rwx` on it"
+	)]
+	SecretFilePerms(String, path::PathBuf, u32),
+	#[error("Command '{1}' for variable '{0}' failed with {2}")]
+	SecretCmd(String, String, process::ExitStatus),
 
 	#[error("Environment doesn't exist")]
 	NoExists,
@@ -51,13 +87,50 @@ pub enum Err {
 	BinChanged(path::PathBuf, path::PathBuf, path::PathBuf),
 }
 
+fn fmt_missing(missing: &[(String, InheritTable)]) -> String {
+	missing
+		.iter()
+		.map(|(var_host, inherit)| {
+			let mut line = format!("\n  {var_host}");
+			if *var_host != inherit.name {
+				line.push_str(&format!(" (as {})", inherit.name));
+			}
+			if let Some(message) = &inherit.message {
+				line.push_str(&format!(": {message}"));
+			}
+			line
+		})
+		.collect()
+}
+
 #[non_exhaustive]
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
+/// Configuration of an environment, deserialized from its config.toml.
 pub struct Root {
+	/// Version of the config's schema, configs without it are version 0 and
+	/// get migrated to the latest version.
+	#[serde(default)]
+	pub version: u32,
+
+	/// The shell started by `cr use`.
 	pub shell: Shell,
+
+	/// Environment variables of the shell.
 	pub vars: Vars,
+
+	/// Binaries available in the shell's PATH.
 	pub bin: Bin,
+
+	/// Files written into the environment on every `cr use`, keyed by their
+	/// path. Relative paths are relative to the shell's home directory, which
+	/// can't be the user's, and absolute paths must be in the environment's
+	/// data directory. Files removed from the table are deleted.
+	pub files: HashMap<path::PathBuf, FileSource>,
+
+	/// Commands run when entering and leaving the environment.
+	pub hooks: Hooks,
 }
 
 impl Default for Root {
@@ -69,71 +142,422 @@ impl Default for Root {
 impl Root {
 	pub fn new() -> Self {
 		Self {
+			version: migrate::VERSION,
 			shell: Shell::default(),
 			vars: Vars::default(),
 			bin: Bin::default(),
+			files: HashMap::new(),
+			hooks: Hooks::default(),
 		}
 	}
 
+	/// Config written for environments created by `cr new`, which unlike
+	/// the defaults of missing keys gives the shell a usable terminal.
+	pub fn new_env() -> Self {
+		let mut env_table = Self::new();
+		env_table.vars.baseline = Baseline::Terminal;
+		env_table
+	}
+
 	/// Deserialize from the environment's config.toml.
-	pub fn from_env(name: &str, dirs: &xdg::BaseDirectories) -> Result<Self> {
-		let shell_env = senv::Senv::new_xdg(name, dirs)?;
+	pub fn from_env(
+		name: &str,
+		dirs: &xdg::BaseDirectories,
+		strict: bool,
+	) -> Result<Self> {
+		let shell_env = senv::Senv::from_name(name, dirs)?;
+		Self::from_senv(&shell_env, strict)
+	}
+
+	/// Deserialize from the config.toml of `shell_env`.
+	pub fn from_senv(shell_env: &senv::Senv, strict: bool) -> Result<Self> {
 		shell_env.is_valid()?;
+		Self::from_file(&shell_env.files.cfg_file, strict)
+	}
 
-		let env_cfg: Self =
-			toml::from_str(&fs::read_to_string(shell_env.files.cfg_file).dp()?)
-				.dp()?;
+	/// Deserialize from a config file, unknown keys are printed as warnings or
+	/// returned as an error if `strict` is true. Older configs are migrated in
+	/// memory, the file is only rewritten by `cr migrate`.
+	pub fn from_file(file: &path::Path, strict: bool) -> Result<Self> {
+		let src = fs::read_to_string(file).dp()?;
+		Self::from_src(file, src, strict)
+	}
+
+	/// Like `from_file` without any warnings, for configs which are only
+	/// looked at in passing.
+	pub fn from_file_quiet(file: &path::Path) -> Result<Self> {
+		let src = fs::read_to_string(file)?;
+		let src = match migrate::migrate_str(&src) {
+			Ok(Some(migrated)) => migrated.src,
+			_ => src,
+		};
+		Ok(Self::parse(file, &src)?.0)
+	}
+
+	/// Like `from_file` with `src` as the contents of `file`.
+	pub fn from_src(
+		file: &path::Path,
+		src: String,
+		strict: bool,
+	) -> Result<Self> {
+		let src = match migrate::migrate_str(&src) {
+			Ok(None) => src,
+			Ok(Some(migrated)) => {
+				eprintln!(
+					"Warning: '{}' is version {}, run `cr migrate` to update \
+					 it to {}",
+					file.display(),
+					migrated.from,
+					migrated.to
+				);
+				migrated.src
+			}
+			// Syntax errors are reported with more context by `parse`.
+			Err(migrate::Err::Toml(_)) => src,
+			Err(err) => return Err(Err::Migrate(err)),
+		};
+		let (env_cfg, unknown) = Self::parse(file, &src).dp()?;
+
+		if strict && !unknown.is_empty() {
+			return Err(Err::UnknownKeys(diag::Diags(unknown))).dp();
+		}
+		for diag in unknown {
+			eprintln!("Warning: {diag}");
+		}
 
 		Ok(env_cfg)
 	}
 
-	/// Return a `Vec` of arguments to be used for a shell based on the
-	/// environment's config.toml.
-	pub fn get_shell_args(
+	/// Deserialize from `src`, the contents of the config file `file`, and
+	/// also return a diagnostic for every key which doesn't correspond to any
+	/// field.
+	pub fn parse(
+		file: &path::Path,
+		src: &str,
+	) -> Result<(Self, Vec<diag::Diag>)> {
+		let mut unknown: Vec<String> = Vec::new();
+		let env_cfg: Self =
+			serde_ignored::deserialize(toml::Deserializer::new(src), |path| {
+				unknown.push(path.to_string());
+			})
+			.map_err(|err| {
+				Err::Parse(Box::new(diag::Diag::new(
+					file,
+					src,
+					err.span(),
+					err.message(),
+				)))
+			})?;
+
+		if env_cfg.version > migrate::VERSION {
+			return Err(Err::Migrate(migrate::Err::Newer(env_cfg.version)));
+		}
+
+		// `src` was already parsed successfully so this can't fail.
+		let doc = toml_edit::ImDocument::parse(src).ok();
+		let diags = unknown
+			.iter()
+			.map(|key| {
+				let span = doc.as_ref().and_then(|doc| key_span(doc, key));
+				let (parent, name) = key.rsplit_once('.').unwrap_or(("", key));
+				let msg = match diag::suggest(name, Self::fields_at(parent)) {
+					Some(field) => {
+						format!("Unknown key '{key}', did you mean '{field}'?")
+					}
+					None => format!("Unknown key '{key}'"),
+				};
+				diag::Diag::new(file, src, span, &msg)
+			})
+			.collect();
+
+		Ok((env_cfg, diags))
+	}
+
+	/// JSON Schema of the config file, generated from the types in this
+	/// module.
+	pub fn json_schema() -> schemars::schema::RootSchema {
+		let mut schema = schemars::schema_for!(Self);
+		schema.schema.metadata().title =
+			Some(String::from("Cleanroom environment config"));
+		schema
+	}
+
+	/// Names of the fields of the table at the dotted `path`.
+	pub fn fields_at(path: &str) -> &'static [&'static str] {
+		match path {
+			"" => diag::struct_fields::<Self>(),
+			"shell" => diag::struct_fields::<Shell>(),
+			"shell.rc" => diag::struct_fields::<Rc>(),
+			"vars" => diag::struct_fields::<Vars>(),
+			"bin" => diag::struct_fields::<Bin>(),
+			"hooks" => diag::struct_fields::<Hooks>(),
+			_ => &[],
+		}
+	}
+
+	/// Return the environment variables of the shell, including the ones of
+	/// `vars.baseline`, `PATH` made of the environment's bin directory and
+	/// `bin.inherit_dirs`, and the `CLEANROOM_*` ones.
+	pub fn to_env(
 		&self,
-		name: &str,
-		dirs: &xdg::BaseDirectories,
-	) -> Result<Vec<String>> {
-		let env_dir = dirs.get_config_home().join(name);
-		let mut args: Vec<String> = Vec::new();
-
-		if self.shell.noprofile {
-			args.push("--noprofile".to_owned());
-		}
-
-		let rc_file = env_dir.join("rc.sh");
-		let rc_file = rc_file
-			.to_str()
-			.ok_or(files::Err::PathToStr)
-			.dp()?
-			.to_owned();
-		if !self.shell.norc && self.shell.interactive {
-			args.push("--rcfile".to_owned());
-			args.push(rc_file);
-			args.push("-i".to_owned());
-		} else {
-			args.push("--norc".to_owned());
+		shell_env: &senv::Senv,
+	) -> Result<HashMap<ffi::OsString, ffi::OsString>> {
+		let files = &shell_env.files;
+		let mut vars = self.vars.to_env(&files.cfg_dir)?;
+		for (var, val) in self.vars.baseline.to_env(&self.shell.bin) {
+			if !self.vars.is_denied(&var.to_string_lossy()) {
+				vars.entry(var).or_insert(val);
+			}
+		}
+		let mut shell_vars = Vec::new();
+		if let Some(home_dir) = self.home_dir(shell_env) {
+			shell_vars.extend(
+				home_vars(&home_dir)
+					.map(|(var, dir)| (var, dir.into_os_string())),
+			);
+		}
+		shell_vars.extend(self.history_vars(shell_env));
+		shell_vars.extend(self.rc_vars(shell_env));
+		for (var, val) in shell_vars {
+			if !self.vars.set.contains_key(var) {
+				vars.insert(var.into(), val);
+			}
+		}
+		vars.insert("PATH".into(), self.bin.path(&files.bin_dir));
+		self.vars.compose(&mut vars);
+		if self.vars.cleanroom_vars {
+			vars.extend(
+				shell_env
+					.cleanroom_vars()
+					.into_iter()
+					.map(|(var, val)| (var.into(), val)),
+			);
+		}
+		Ok(vars)
+	}
+
+	/// Return the home directory of the shell, `None` if it's the user's.
+	pub fn home_dir(&self, shell_env: &senv::Senv) -> Option<path::PathBuf> {
+		match &self.shell.home {
+			Home::Host => None,
+			Home::Private => Some(shell_env.files.home_dir.clone()),
+			Home::Path(path) => {
+				Some(files::resolve(path, &shell_env.files.cfg_dir))
+			}
+		}
+	}
+
+	/// Variables which make the shell keep its history as set by
+	/// `shell.history`.
+	pub fn history_vars(
+		&self,
+		shell_env: &senv::Senv,
+	) -> Vec<(&'static str, ffi::OsString)> {
+		match self.shell.history {
+			History::Shared => Vec::new(),
+			History::Isolated => vec![
+				(
+					"HISTFILE",
+					shell_env.files.history_file.clone().into_os_string(),
+				),
+				("fish_history", fish_session(shell_env).into()),
+			],
+			// An empty `HISTFILE` stops bash and zsh from saving the history
+			// without changing the default of `HISTSIZE`.
+			History::None => {
+				vec![("HISTFILE", "".into()), ("fish_history", "".into())]
+			}
+		}
+	}
+
+	/// The file the shell saves its history to if `shell.history` is
+	/// "isolated", `None` if it's in the user's home directory and `HOME`
+	/// isn't set.
+	pub fn history_file(
+		&self,
+		shell_env: &senv::Senv,
+	) -> Option<path::PathBuf> {
+		if self.shell.flavor() != Flavor::Fish {
+			return Some(shell_env.files.history_file.clone());
+		}
+
+		// fish keeps the history of a session in its data directory.
+		let data_home = match self.home_dir(shell_env) {
+			Some(home_dir) => home_dir.join(".local/share"),
+			None => match env::var_os("XDG_DATA_HOME") {
+				Some(dir) if !dir.is_empty() => dir.into(),
+				_ => path::PathBuf::from(env::var_os("HOME")?)
+					.join(".local/share"),
+			},
+		};
+		Some(
+			data_home
+				.join("fish")
+				.join(format!("{}_history", fish_session(shell_env))),
+		)
+	}
+
+	/// Expand a directory of `shell.cwd` or `shell.cwd_allowed` with the
+	/// environment's variables `vars`.
+	pub fn expand_dir(
+		&self,
+		dir: &str,
+		shell_env: &senv::Senv,
+		vars: &HashMap<ffi::OsString, ffi::OsString>,
+	) -> Result<path::PathBuf> {
+		let expanded = interp::render_os(dir, vars)
+			.map_err(|err| Err::Interp(dir.to_owned(), err))?;
+		Ok(files::resolve(
+			path::Path::new(&expanded),
+			&shell_env.files.cfg_dir,
+		))
+	}
+
+	/// Create the home directory of the shell and its XDG base directories,
+	/// if it isn't the user's.
+	pub fn create_home(&self, shell_env: &senv::Senv) -> Result<()> {
+		if let Some(home_dir) = self.home_dir(shell_env) {
+			for (_, dir) in home_vars(&home_dir) {
+				fs::create_dir_all(dir).dp()?;
+			}
+		}
+		Ok(())
+	}
+
+	/// Return a `Vec` of arguments to be used for a shell based on the
+	/// environment's config.toml, the options of each flavor of shell.
+	pub fn get_shell_args(&self, shell_env: &senv::Senv) -> Vec<ffi::OsString> {
+		let flavor = self.shell.flavor();
+		let rc_file = rc::path(flavor, shell_env).into_os_string();
+		let use_rc = !self.shell.norc && self.shell.interactive;
+		let mut args: Vec<ffi::OsString> = Vec::new();
+
+		match flavor {
+			Flavor::Bash => {
+				if self.shell.noprofile {
+					args.push("--noprofile".into());
+				}
+				if use_rc {
+					args.push("--rcfile".into());
+					args.push(rc_file);
+					args.push("-i".into());
+				} else {
+					args.push("--norc".into());
+				}
+			}
+			// zsh reads the rc file from `ZDOTDIR`.
+			Flavor::Zsh => {
+				if self.shell.noprofile {
+					args.push("--no-globalrcs".into());
+				}
+				if use_rc {
+					args.push("-i".into());
+				} else {
+					args.push("--no-rcs".into());
+				}
+			}
+			Flavor::Fish => {
+				if use_rc {
+					let mut init =
+						ffi::OsString::from("--init-command=source ");
+					init.push(rc::quote(flavor, &rc_file));
+					args.push(init);
+					args.push("-i".into());
+				} else {
+					args.push("--no-config".into());
+				}
+			}
+			// sh reads the rc file from `ENV`.
+			Flavor::Sh => {
+				if use_rc {
+					args.push("-i".into());
+				}
+			}
 		}
 
 		if self.shell.login {
-			args.push("-l".to_owned());
+			args.push("-l".into());
 		}
 
-		Ok(args)
+		args
+	}
+
+	/// Variables which make the shell read the generated rc file, for the
+	/// shells which don't take it as an argument.
+	pub fn rc_vars(
+		&self,
+		shell_env: &senv::Senv,
+	) -> Vec<(&'static str, ffi::OsString)> {
+		if self.shell.norc || !self.shell.interactive {
+			return Vec::new();
+		}
+		match self.shell.flavor() {
+			Flavor::Zsh => {
+				vec![("ZDOTDIR", shell_env.files.rc_dir.clone().into())]
+			}
+			Flavor::Sh => {
+				vec![("ENV", rc::path(Flavor::Sh, shell_env).into_os_string())]
+			}
+			Flavor::Bash | Flavor::Fish => Vec::new(),
+		}
 	}
 }
 
 #[allow(clippy::struct_excessive_bools)]
 #[non_exhaustive]
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 // TODO: add field for prompt and use it as a template to generate the prompt.
 pub struct Shell {
+	/// Path of the shell's binary, looked up in PATH if it isn't absolute.
 	pub bin: String,
+
+	/// Whether to start the shell as a login shell.
 	pub login: bool,
+
+	/// Whether to start the shell in interactive mode.
 	pub interactive: bool,
+
+	/// Whether to skip the system and user profile files.
 	pub noprofile: bool,
+
+	/// Whether to skip the environment's rc file.
 	pub norc: bool,
+
+	/// Contents of the rc file generated on every `cr use`, which also
+	/// sources the hand-written `rc.sh` (`rc.fish` for fish) in the
+	/// environment's config directory.
+	pub rc: Rc,
+
+	/// What to do when using the environment from within another one, which
+	/// is detected with `CLEANROOM_DEPTH`.
+	pub nested: Nested,
+
+	/// Home directory of the shell, "host" keeps the user's, "private" uses a
+	/// directory in the environment's data directory and any other value is
+	/// the path of a directory. `~` is the user's home directory and relative
+	/// paths are relative to the environment's config directory. Except for
+	/// "host", `HOME` and the XDG base directories point into it unless
+	/// they're in `vars.set`.
+	pub home: Home,
+
+	/// Where the shell keeps its history, set with `HISTFILE` for bash and zsh
+	/// and `fish_history` for fish unless they're in `vars.set`.
+	pub history: History,
+
+	/// Directory the shell starts in, `${VAR}` is replaced by the value of
+	/// the environment's variable `VAR`, `~` is the user's home directory and
+	/// relative paths are relative to the environment's config directory.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	pub cwd: Option<String>,
+
+	/// When to start the shell in `cwd`.
+	pub cwd_policy: CwdPolicy,
+
+	/// Directories, other than `cwd`, in which the shell may start if
+	/// `cwd_policy` is "restrict". Expanded like `cwd`.
+	pub cwd_allowed: Vec<String>,
 }
 
 impl Default for Shell {
@@ -150,25 +574,230 @@ impl Shell {
 			interactive: true,
 			noprofile: true,
 			norc: false,
+			rc: Rc::default(),
+			nested: Nested::default(),
+			home: Home::default(),
+			history: History::default(),
+			cwd: None,
+			cwd_policy: CwdPolicy::default(),
+			cwd_allowed: Vec::new(),
+		}
+	}
+
+	/// The flavor of the shell, from the file name of `bin`.
+	pub fn flavor(&self) -> Flavor {
+		let name = path::Path::new(&self.bin)
+			.file_name()
+			.map(|name| name.to_string_lossy().into_owned())
+			.unwrap_or_default();
+		match name.as_str() {
+			"bash" => Flavor::Bash,
+			"zsh" => Flavor::Zsh,
+			"fish" => Flavor::Fish,
+			_ => Flavor::Sh,
+		}
+	}
+}
+
+/// Shells with different options and rc file syntax.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Flavor {
+	/// Any POSIX shell.
+	Sh,
+	Bash,
+	Zsh,
+	Fish,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct Rc {
+	/// Files sourced before everything else, `~` is the user's home directory
+	/// and relative paths are relative to the environment's config directory.
+	#[serde(with = "os_paths")]
+	#[schemars(with = "Vec<OsValue>")]
+	pub source: Vec<path::PathBuf>,
+
+	/// Functions by name, the values are their bodies.
+	pub functions: HashMap<String, String>,
+
+	/// Aliases by name, the values are the commands they expand to.
+	pub aliases: HashMap<String, String>,
+
+	/// Code added as is after the aliases, in order.
+	pub snippets: Vec<String>,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum Home {
+	/// The `home` directory in the environment's data directory.
+	Private,
+	/// The user's home directory.
+	#[default]
+	Host,
+	/// Any other directory.
+	Path(path::PathBuf),
+}
+
+impl Serialize for Home {
+	fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		match self {
+			Self::Private => serializer.serialize_str("private"),
+			Self::Host => serializer.serialize_str("host"),
+			Self::Path(path) => os_path::serialize(path, serializer),
 		}
 	}
 }
 
+impl<'de> Deserialize<'de> for Home {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		let path = os_path::deserialize(deserializer)?;
+		Ok(match path.to_str() {
+			Some("private") => Self::Private,
+			Some("host") => Self::Host,
+			_ => Self::Path(path),
+		})
+	}
+}
+
+impl JsonSchema for Home {
+	fn schema_name() -> String {
+		String::from("Home")
+	}
+
+	fn json_schema(
+		gen: &mut schemars::gen::SchemaGenerator,
+	) -> schemars::schema::Schema {
+		OsValue::json_schema(gen)
+	}
+}
+
 #[non_exhaustive]
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(
+	Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum History {
+	/// A history file in the environment's data directory, for fish a
+	/// session named after the environment.
+	Isolated,
+	/// The shell's default history, usually shared with the user's shells.
+	#[default]
+	Shared,
+	/// Don't save the history.
+	None,
+}
+
+#[non_exhaustive]
+#[derive(
+	Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum CwdPolicy {
+	/// Always start in `cwd`.
+	#[default]
+	Always,
+	/// Start in `cwd` unless the current directory is already in it.
+	Outside,
+	/// Stay in the current directory, or the one given with `--cd`, and
+	/// refuse to start unless it's in `cwd` or one of `cwd_allowed`.
+	Restrict,
+}
+
+#[non_exhaustive]
+#[derive(
+	Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Nested {
+	/// Start the shell.
+	Allow,
+	/// Print a warning and start the shell.
+	#[default]
+	Warn,
+	/// Exit with an error.
+	Deny,
+}
+
+#[non_exhaustive]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 // TODO: Add field `clear: bool` if environment variables should be cleared.
 pub struct Vars {
-	/// Environment variables that will be inherited from the parent process.
-	pub inherit: Vec<String>,
+	/// Preset of well-known variables the environment starts with, the other
+	/// settings override them.
+	pub baseline: Baseline,
+
+	/// Environment variables that will be inherited from the parent process,
+	/// either names or tables with per-variable options. Names containing `*`
+	/// or `?` are glob patterns matching any number of characters or a single
+	/// character, and aren't considered missing if nothing matches.
+	pub inherit: Vec<Inherit>,
+
+	/// Environment variables to inherit from the parent process under a
+	/// different name, similar to `inherit` except that the key is the name in
+	/// the parent and the value is the name (or table with `name`) in the
+	/// environment.
+	pub inherit_rename: HashMap<String, Inherit>,
+
+	/// Whether to inherit every variable from the parent process, except the
+	/// ones in `deny`, instead of starting from an empty environment.
+	pub inherit_all: bool,
+
+	/// Names or glob patterns of variables which are never inherited, even if
+	/// they're in `inherit` or `inherit_all` is true.
+	pub deny: Vec<String>,
 
 	/// Whether to exit when the variable which is supposed to be inherited is
-	/// missing from the parent environment.
+	/// missing from the parent environment. Used for the entries in `inherit`
+	/// which don't set `required`.
 	pub exit_on_missing: bool,
 
-	/// Environment variables custom to this config/started shell.
-	/// Overrides the ones set in `inherit`.
-	pub set: HashMap<String, String>,
+	/// Dotenv files to load variables from, later files override earlier ones.
+	/// `~` is the home directory and relative paths are relative to the
+	/// environment's config directory. Overrides the ones set in `inherit`.
+	#[serde(with = "os_paths")]
+	#[schemars(with = "Vec<OsValue>")]
+	pub dotenv: Vec<path::PathBuf>,
+
+	/// Environment variables custom to this config/started shell, values
+	/// which aren't valid unicode can be set with `{ escaped = "..." }`.
+	/// Overrides the ones set in `inherit` and `dotenv`.
+	pub set: HashMap<String, OsValue>,
+
+	/// Environment variables read from files, e.g. tokens, so they don't have
+	/// to be stored in the config. The file must not be readable by group or
+	/// others. Overrides the ones set in `inherit`, `dotenv` and `set`.
+	pub from_file: HashMap<String, FromFile>,
+
+	/// Environment variables read from the stdout of a command run with
+	/// `/bin/sh -c` on the host, e.g. `pass show token`. Overrides the ones
+	/// set in `inherit`, `dotenv`, `set` and `from_file`.
+	pub from_cmd: HashMap<String, FromCmd>,
+
+	/// Entries added to the front of list variables like `MANPATH`, either
+	/// lists or tables with more options. Composed with the value the
+	/// variable has after all of the above, `PATH` included.
+	pub prepend: HashMap<String, List>,
+
+	/// Entries added to the end of list variables, similar to `prepend`.
+	pub append: HashMap<String, List>,
+
+	/// Whether to set `CLEANROOM_ENV`, `CLEANROOM_CONFIG_DIR`,
+	/// `CLEANROOM_DATA_DIR`, `CLEANROOM_BIN_DIR`, `CLEANROOM_DEPTH` and
+	/// `CLEANROOM_SESSION`, which override all other variables.
+	pub cleanroom_vars: bool,
 }
 
 impl Default for Vars {
@@ -180,66 +809,995 @@ impl Default for Vars {
 impl Vars {
 	pub fn new() -> Self {
 		Self {
+			baseline: Baseline::default(),
 			inherit: Vec::new(),
+			inherit_rename: HashMap::new(),
+			inherit_all: false,
+			deny: Vec::new(),
 			exit_on_missing: true,
+			dotenv: Vec::new(),
 			set: HashMap::new(),
+			from_file: HashMap::new(),
+			from_cmd: HashMap::new(),
+			prepend: HashMap::new(),
+			append: HashMap::new(),
+			cleanroom_vars: true,
 		}
 	}
 
-	#[allow(
-		clippy::missing_panics_doc,
-		clippy::unwrap_in_result,
-		clippy::unwrap_used
-	)]
-	/// Return the key, value pair for environment variables.
-	pub fn to_env(&self) -> Result<HashMap<String, String>> {
-		let mut vars = HashMap::<String, String>::new();
+	/// Return the key, value pair for environment variables. Relative paths
+	/// are relative to `cfg_dir`, the environment's config directory.
+	pub fn to_env(
+		&self,
+		cfg_dir: &path::Path,
+	) -> Result<HashMap<ffi::OsString, ffi::OsString>> {
+		let mut vars = HashMap::<ffi::OsString, ffi::OsString>::new();
 
-		if self.exit_on_missing {
-			for var in self.inherit.clone() {
-				let val = match env::var(&var) {
-					Ok(ok) => ok,
-					Err(err) => match err {
-						env::VarError::NotPresent => {
-							return Err(Err::EnvVarNotPresent(var)).dp();
-						}
-						env::VarError::NotUnicode(data) => {
-							return Err(Err::EnvVarNotUnicode(data)).dp();
-						}
-					},
-				};
+		if self.inherit_all {
+			vars.extend(env::vars_os());
+		}
 
-				vars.insert(var, val);
+		let mut missing: Vec<(String, InheritTable)> = Vec::new();
+		for (var_host, inherit) in self.inherit_entries() {
+			let var = &inherit.name;
+
+			if is_glob(var) {
+				vars.extend(env::vars_os().filter(|(var_host, _)| {
+					glob_match(var, &var_host.to_string_lossy())
+				}));
+				continue;
 			}
-		} else {
-			for var in self.inherit.clone() {
-				let val = env::var(var.clone());
-				if val.is_err() {
-					continue;
+			if self.is_denied(&var_host) || self.is_denied(var) {
+				continue;
+			}
+
+			match env::var_os(&var_host) {
+				Some(val) => {
+					vars.insert(var.into(), val);
+				}
+				None => {
+					if let Some(default) = &inherit.default {
+						vars.insert(var.into(), default.0.clone());
+					} else if self.is_required(&inherit)
+						&& !self.set.contains_key(var)
+					{
+						missing.push((var_host, inherit));
+					}
 				}
-				vars.insert(var, val.unwrap());
 			}
 		}
 
-		vars.extend(self.set.clone());
+		// Report every missing variable instead of only the first one.
+		if !missing.is_empty() {
+			return Err(Err::EnvVarsNotPresent(missing)).dp();
+		}
+
+		vars.retain(|var, _| !self.is_denied(&var.to_string_lossy()));
+
+		for dotenv_file in &self.dotenv {
+			let dotenv_file = files::resolve(dotenv_file, cfg_dir);
+			vars.extend(
+				dotenv::read(&dotenv_file)
+					.dp()?
+					.into_iter()
+					.map(|(var, val)| (var.into(), val.into())),
+			);
+		}
+
+		vars.extend(
+			self.set
+				.iter()
+				.map(|(var, val)| (var.into(), val.0.clone())),
+		);
+
+		for (var, from_file) in &self.from_file {
+			vars.insert(var.into(), from_file.read(var, cfg_dir)?);
+		}
+		for (var, from_cmd) in &self.from_cmd {
+			vars.insert(var.into(), from_cmd.read(var, cfg_dir)?);
+		}
 
 		Ok(vars)
 	}
+
+	/// Add the entries of `prepend` and `append` to the list variables in
+	/// `vars`. `~/` at the start of an entry is the home directory.
+	pub fn compose(&self, vars: &mut HashMap<ffi::OsString, ffi::OsString>) {
+		let mut names: Vec<&String> =
+			self.prepend.keys().chain(self.append.keys()).collect();
+		names.sort();
+		names.dedup();
+
+		for var in names {
+			let prepend = self.prepend.get(var).map(List::table);
+			let append = self.append.get(var).map(List::table);
+			let sep = [&prepend, &append]
+				.iter()
+				.find_map(|list| list.as_ref()?.separator.as_deref())
+				.unwrap_or(":");
+			let dedup = [&prepend, &append]
+				.iter()
+				.any(|list| list.as_ref().is_some_and(|list| list.dedup));
+
+			let current = vars
+				.get(ffi::OsStr::new(var))
+				.map_or(&[][..], |val| val.as_bytes());
+			let entries: Vec<ffi::OsString> = prepend
+				.iter()
+				.flat_map(ListTable::entries)
+				.chain(
+					split_bytes(current, sep.as_bytes())
+						.filter(|entry| !entry.is_empty())
+						.map(|entry| ffi::OsStr::from_bytes(entry).to_owned()),
+				)
+				.chain(append.iter().flat_map(ListTable::entries))
+				.collect();
+
+			let mut composed: Vec<ffi::OsString> = Vec::new();
+			for entry in entries {
+				if !dedup || !composed.contains(&entry) {
+					composed.push(entry);
+				}
+			}
+			vars.insert(var.into(), composed.join(ffi::OsStr::new(sep)));
+		}
+	}
+
+	/// The entries of `inherit` and `inherit_rename` as the name of the
+	/// variable in the parent and the options for it in the environment.
+	pub fn inherit_entries(&self) -> Vec<(String, InheritTable)> {
+		let inherit = self.inherit.iter().map(|inherit| {
+			let inherit = inherit.table();
+			(inherit.name.clone(), inherit)
+		});
+		let mut inherit_rename: Vec<(String, InheritTable)> = self
+			.inherit_rename
+			.iter()
+			.map(|(var_host, inherit)| (var_host.clone(), inherit.table()))
+			.collect();
+		inherit_rename.sort_by(|lhs, rhs| lhs.0.cmp(&rhs.0));
+
+		inherit.chain(inherit_rename).collect()
+	}
+
+	/// Whether the parent must have the variable, if it doesn't have a default.
+	pub fn is_required(&self, inherit: &InheritTable) -> bool {
+		inherit.required.unwrap_or(self.exit_on_missing)
+	}
+
+	/// Whether `var` matches a name or pattern in `deny`.
+	pub fn is_denied(&self, var: &str) -> bool {
+		self.deny.iter().any(|deny| glob_match(deny, var))
+	}
+}
+
+/// Commands are either strings or tables with more options, and run with
+/// `/bin/sh -c` and the environment's variables.
+#[non_exhaustive]
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct Hooks {
+	/// Commands run on the host before the shell starts.
+	pub pre_enter: Vec<Hook>,
+
+	/// Commands run by the shell's generated rc file after everything else,
+	/// so they can change the shell, e.g. `eval "$(ssh-agent)"`. They're
+	/// written in the shell's syntax, only run if the rc file is used and
+	/// `timeout` doesn't apply to them.
+	pub post_enter: Vec<Hook>,
+
+	/// Commands run on the host after the shell exits, with the shell's exit
+	/// code in `CLEANROOM_EXIT_CODE`.
+	pub post_exit: Vec<Hook>,
+}
+
+/// Either the command or a table with more options.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum Hook {
+	Cmd(String),
+	Table(HookTable),
+}
+
+impl<'de> Deserialize<'de> for Hook {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(match plain_or_table(deserializer, |_| false)? {
+			PlainOrTable::Plain(cmd) => Self::Cmd(cmd),
+			PlainOrTable::Table(table) => Self::Table(table),
+		})
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct HookTable {
+	/// The command.
+	pub cmd: String,
+
+	/// What to do if the command fails.
+	#[serde(default)]
+	pub on_failure: OnFailure,
+
+	/// Seconds to wait for the command before it fails, 0 waits indefinitely.
+	#[serde(default = "HookTable::default_timeout")]
+	pub timeout: u64,
+}
+
+impl HookTable {
+	fn default_timeout() -> u64 {
+		30
+	}
+}
+
+impl Hook {
+	pub fn table(&self) -> HookTable {
+		match self {
+			Self::Cmd(cmd) => HookTable {
+				cmd: cmd.clone(),
+				on_failure: OnFailure::default(),
+				timeout: HookTable::default_timeout(),
+			},
+			Self::Table(table) => table.clone(),
+		}
+	}
+}
+
+#[non_exhaustive]
+#[derive(
+	Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OnFailure {
+	/// Stop, `pre_enter` doesn't start the shell, `post_enter` exits the shell
+	/// and `post_exit` skips the remaining commands and exits with an error.
+	#[default]
+	Abort,
+	/// Print a warning and continue.
+	Warn,
+	/// Continue silently.
+	Ignore,
+}
+
+/// Where the contents of a file in `files` come from.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum FileSource {
+	/// Inline contents, in which `${VAR}` is replaced by the value of the
+	/// environment's variable `VAR` and `$$` by `$`. Only the user can read
+	/// the written file.
+	Content(String),
+	/// Template file like `content`, relative paths are relative to the
+	/// environment's config directory.
+	#[serde(with = "os_path")]
+	#[schemars(with = "OsValue")]
+	Template(path::PathBuf),
+	/// File on the host copied as is with its mode, `~` is the user's home
+	/// directory and relative paths are relative to the environment's config
+	/// directory.
+	#[serde(with = "os_path")]
+	#[schemars(with = "OsValue")]
+	Copy(path::PathBuf),
+	/// File on the host which is symlinked, like `copy`.
+	#[serde(with = "os_path")]
+	#[schemars(with = "OsValue")]
+	Link(path::PathBuf),
+}
+
+/// Name of the fish history session of an environment, fish only allows
+/// letters, digits and `_` in it.
+fn fish_session(shell_env: &senv::Senv) -> String {
+	format!("cleanroom_{}", shell_env.name)
+		.replace(|ch: char| !ch.is_ascii_alphanumeric(), "_")
+}
+
+/// `HOME` and the XDG base directories for the home directory `home_dir`.
+fn home_vars(home_dir: &path::Path) -> [(&'static str, path::PathBuf); 5] {
+	[
+		("HOME", home_dir.to_owned()),
+		("XDG_CONFIG_HOME", home_dir.join(".config")),
+		("XDG_CACHE_HOME", home_dir.join(".cache")),
+		("XDG_DATA_HOME", home_dir.join(".local/share")),
+		("XDG_STATE_HOME", home_dir.join(".local/state")),
+	]
+}
+
+/// Presets of well-known variables, every preset includes the ones of the
+/// previous preset.
+#[non_exhaustive]
+#[derive(
+	Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Baseline {
+	/// No variables.
+	#[default]
+	None,
+	/// `HOME`, `USER` and `LOGNAME` from the passwd entry of the user and
+	/// `SHELL` from `shell.bin`.
+	Minimal,
+	/// Also inherits `TERM`, `COLORTERM`, `LANG`, `LANGUAGE`, `LC_*` and `TZ`.
+	Terminal,
+	/// Also inherits the variables needed to connect to the display server and
+	/// the session bus, e.g. `DISPLAY` and `WAYLAND_DISPLAY`.
+	Desktop,
+}
+
+impl Baseline {
+	/// Variables inherited by `terminal`.
+	const TERMINAL: &'static [&'static str] =
+		&["TERM", "COLORTERM", "LANG", "LANGUAGE", "LC_*", "TZ"];
+
+	/// Variables inherited by `desktop` on top of the ones of `terminal`.
+	const DESKTOP: &'static [&'static str] = &[
+		"DISPLAY",
+		"WAYLAND_DISPLAY",
+		"XAUTHORITY",
+		"XDG_RUNTIME_DIR",
+		"XDG_SESSION_TYPE",
+		"XDG_CURRENT_DESKTOP",
+		"DBUS_SESSION_BUS_ADDRESS",
+	];
+
+	/// Names and glob patterns of the variables inherited by the preset, if
+	/// the parent has them.
+	pub fn inherited(self) -> impl Iterator<Item = &'static str> {
+		let (terminal, desktop): (&[_], &[_]) = match self {
+			Self::None | Self::Minimal => (&[], &[]),
+			Self::Terminal => (Self::TERMINAL, &[]),
+			Self::Desktop => (Self::TERMINAL, Self::DESKTOP),
+		};
+		terminal.iter().chain(desktop).copied()
+	}
+
+	/// Return the variables of the preset, `shell` is the environment's shell.
+	/// The user's variables are taken from the parent if the user doesn't have
+	/// a passwd entry.
+	pub fn to_env(self, shell: &str) -> HashMap<ffi::OsString, ffi::OsString> {
+		let mut vars = HashMap::new();
+		if self == Self::None {
+			return vars;
+		}
+
+		match user::current() {
+			Some(passwd) => {
+				vars.insert("HOME".into(), passwd.home.into_os_string());
+				vars.insert("USER".into(), passwd.name.clone());
+				vars.insert("LOGNAME".into(), passwd.name);
+			}
+			None => {
+				for var in ["HOME", "USER", "LOGNAME"] {
+					if let Some(val) = env::var_os(var) {
+						vars.insert(var.into(), val);
+					}
+				}
+			}
+		}
+
+		let shell = path::Path::new(shell);
+		let shell = files::bin_get_abs(shell).unwrap_or_else(|_| shell.into());
+		vars.insert("SHELL".into(), shell.into_os_string());
+
+		vars.extend(env::vars_os().filter(|(var, _)| {
+			let var = var.to_string_lossy();
+			self.inherited().any(|pattern| glob_match(pattern, &var))
+		}));
+
+		vars
+	}
+}
+
+/// Either the name of the variable or a table with more options.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum Inherit {
+	Name(String),
+	Table(InheritTable),
+}
+
+impl<'de> Deserialize<'de> for Inherit {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(match plain_or_table(deserializer, |_| false)? {
+			PlainOrTable::Plain(name) => Self::Name(name),
+			PlainOrTable::Table(table) => Self::Table(table),
+		})
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct InheritTable {
+	/// Name or glob pattern of the variable.
+	pub name: String,
+
+	/// Value to use if the parent doesn't have the variable.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub default: Option<OsValue>,
+
+	/// Whether to exit if the parent doesn't have the variable and there's no
+	/// `default`, overrides `exit_on_missing`.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub required: Option<bool>,
+
+	/// Message shown if the variable is required and missing, e.g. how to set
+	/// it.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub message: Option<String>,
+}
+
+impl Inherit {
+	pub fn table(&self) -> InheritTable {
+		match self {
+			Self::Name(name) => InheritTable {
+				name: name.clone(),
+				default: None,
+				required: None,
+				message: None,
+			},
+			Self::Table(table) => table.clone(),
+		}
+	}
+}
+
+/// Either the entries or a table with more options.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum List {
+	Entries(Vec<OsValue>),
+	Table(ListTable),
+}
+
+impl<'de> Deserialize<'de> for List {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(match plain_or_table(deserializer, |_| false)? {
+			PlainOrTable::Plain(entries) => Self::Entries(entries),
+			PlainOrTable::Table(table) => Self::Table(table),
+		})
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ListTable {
+	/// Entries to add, in order.
+	pub entries: Vec<OsValue>,
+
+	/// Separator between the entries of the variable, `:` if neither
+	/// `prepend` nor `append` set it and the one of `prepend` if both do.
+	#[serde(default, skip_serializing_if = "Option::is_none")]
+	pub separator: Option<String>,
+
+	/// Whether to only keep the first occurrence of every entry of the
+	/// variable.
+	#[serde(default = "ListTable::default_dedup")]
+	pub dedup: bool,
+
+	/// Whether to drop the entries which don't exist on disk.
+	#[serde(default)]
+	pub existing_only: bool,
+}
+
+impl ListTable {
+	fn default_dedup() -> bool {
+		true
+	}
+
+	// The entries with `~` expanded and, if `existing_only`, without the
+	// missing ones.
+	fn entries(&self) -> Vec<ffi::OsString> {
+		self.entries
+			.iter()
+			.map(|entry| {
+				let entry = path::Path::new(&entry.0);
+				if entry.as_os_str().as_bytes().starts_with(b"~/") {
+					files::resolve(entry, path::Path::new("")).into_os_string()
+				} else {
+					entry.as_os_str().to_owned()
+				}
+			})
+			.filter(|entry| {
+				!self.existing_only || path::Path::new(entry).exists()
+			})
+			.collect()
+	}
+}
+
+// Split `val` at every occurrence of `sep`.
+fn split_bytes<'a>(
+	val: &'a [u8],
+	sep: &'a [u8],
+) -> impl Iterator<Item = &'a [u8]> + 'a {
+	let mut rest = Some(val);
+	std::iter::from_fn(move || {
+		let val = rest?;
+		match val.windows(sep.len().max(1)).position(|win| win == sep) {
+			Some(idx) if !sep.is_empty() => {
+				rest = Some(&val[idx + sep.len()..]);
+				Some(&val[..idx])
+			}
+			_ => {
+				rest = None;
+				Some(val)
+			}
+		}
+	})
+}
+
+impl List {
+	pub fn table(&self) -> ListTable {
+		match self {
+			Self::Entries(entries) => ListTable {
+				entries: entries.clone(),
+				separator: None,
+				dedup: ListTable::default_dedup(),
+				existing_only: false,
+			},
+			Self::Table(table) => table.clone(),
+		}
+	}
+}
+
+/// A value which isn't necessarily valid unicode. Strings are used as is, and
+/// a table `{ escaped = "..." }` can contain any byte as `\xHH`, with `\\`
+/// for a backslash. In TOML basic strings the backslashes have to be doubled.
+/// Paths take the same form, except for the ones used as keys in `files` and
+/// `bin.inherit_rename` since TOML keys can only be strings.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OsValue(pub ffi::OsString);
+
+#[derive(Serialize, JsonSchema)]
+#[serde(untagged, deny_unknown_fields)]
+enum OsValueRepr {
+	Str(String),
+	Escaped { escaped: String },
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Escaped {
+	escaped: String,
+}
+
+impl Serialize for OsValue {
+	fn serialize<S>(&self, serializer: S) -> result::Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		match self.0.to_str() {
+			Some(val) => OsValueRepr::Str(val.to_owned()),
+			None => OsValueRepr::Escaped {
+				escaped: escape(&self.0),
+			},
+		}
+		.serialize(serializer)
+	}
+}
+
+impl<'de> Deserialize<'de> for OsValue {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		let val: PlainOrTable<String, Escaped> =
+			plain_or_table(deserializer, |_| false)?;
+		match val {
+			PlainOrTable::Plain(val) => Ok(Self(val.into())),
+			PlainOrTable::Table(Escaped { escaped }) => unescape(&escaped)
+				.map(Self)
+				.map_err(serde::de::Error::custom),
+		}
+	}
+}
+
+impl OsValue {
+	// Whether `table` is the `escaped` form rather than a table with more
+	// options which contains an `OsValue`.
+	fn is_escaped(table: &toml::Table) -> bool {
+		table.contains_key("escaped")
+	}
+}
+
+enum PlainOrTable<P, T> {
+	Plain(P),
+	Table(T),
+}
+
+// Deserialize a value which is either in a plain form or a table, like
+// `#[serde(untagged)]` but keeping the error of the table so it names the
+// offending key. Tables for which `is_plain` is true are the plain form.
+fn plain_or_table<'de, D, P, T>(
+	deserializer: D,
+	is_plain: fn(&toml::Table) -> bool,
+) -> result::Result<PlainOrTable<P, T>, D::Error>
+where
+	D: serde::Deserializer<'de>,
+	P: serde::de::DeserializeOwned,
+	T: serde::de::DeserializeOwned,
+{
+	match toml::Value::deserialize(deserializer)? {
+		toml::Value::Table(table) if !is_plain(&table) => {
+			T::deserialize(toml::Value::Table(table)).map(PlainOrTable::Table)
+		}
+		val => P::deserialize(val).map(PlainOrTable::Plain),
+	}
+	.map_err(|err| serde::de::Error::custom(err.message()))
+}
+
+impl JsonSchema for OsValue {
+	fn schema_name() -> String {
+		String::from("OsValue")
+	}
+
+	fn json_schema(
+		gen: &mut schemars::gen::SchemaGenerator,
+	) -> schemars::schema::Schema {
+		OsValueRepr::json_schema(gen)
+	}
+}
+
+impl From<&str> for OsValue {
+	fn from(val: &str) -> Self {
+		Self(val.into())
+	}
+}
+
+/// (De)serialize a path like an `OsValue`, for `#[serde(with = "os_path")]`.
+mod os_path {
+	use std::path;
+
+	use serde::{Deserialize, Serialize};
+
+	use super::OsValue;
+
+	pub fn serialize<S>(
+		path: &path::Path,
+		serializer: S,
+	) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		OsValue(path.as_os_str().to_owned()).serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D>(
+		deserializer: D,
+	) -> Result<path::PathBuf, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(OsValue::deserialize(deserializer)?.0.into())
+	}
+}
+
+/// Like `os_path`, for lists of paths.
+mod os_paths {
+	use std::path;
+
+	use serde::{Deserialize, Serialize};
+
+	use super::OsValue;
+
+	pub fn serialize<S>(
+		paths: &[path::PathBuf],
+		serializer: S,
+	) -> Result<S::Ok, S::Error>
+	where
+		S: serde::Serializer,
+	{
+		paths
+			.iter()
+			.map(|path| OsValue(path.as_os_str().to_owned()))
+			.collect::<Vec<_>>()
+			.serialize(serializer)
+	}
+
+	pub fn deserialize<'de, D>(
+		deserializer: D,
+	) -> Result<Vec<path::PathBuf>, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(Vec::<OsValue>::deserialize(deserializer)?
+			.into_iter()
+			.map(|val| val.0.into())
+			.collect())
+	}
+}
+
+/// Escape `val` for the `escaped` form of `OsValue`, bytes which aren't valid
+/// unicode become `\xHH`.
+pub fn escape(val: &ffi::OsStr) -> String {
+	let mut escaped = String::new();
+	for chunk in val.as_bytes().utf8_chunks() {
+		escaped.push_str(&chunk.valid().replace('\\', "\\\\"));
+		for byte in chunk.invalid() {
+			escaped.push_str(&format!("\\x{byte:02X}"));
+		}
+	}
+	escaped
+}
+
+/// The inverse of `escape`.
+pub fn unescape(escaped: &str) -> result::Result<ffi::OsString, String> {
+	let mut val = Vec::new();
+	let mut chars = escaped.chars();
+	while let Some(ch) = chars.next() {
+		if ch != '\\' {
+			let mut buf = [0; 4];
+			val.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+			continue;
+		}
+		match chars.next() {
+			Some('\\') => val.push(b'\\'),
+			Some('x') => {
+				let hex: String = chars.by_ref().take(2).collect();
+				let byte = u8::from_str_radix(&hex, 16).map_err(|_| {
+					format!("Invalid escape '\\x{hex}' in '{escaped}'")
+				})?;
+				val.push(byte);
+			}
+			Some(ch) => {
+				return Err(format!("Invalid escape '\\{ch}' in '{escaped}'"));
+			}
+			None => {
+				return Err(format!("Trailing backslash in '{escaped}'"));
+			}
+		}
+	}
+	Ok(ffi::OsString::from_vec(val))
+}
+
+/// Whether `name` contains glob characters.
+pub fn is_glob(name: &str) -> bool {
+	name.contains(['*', '?'])
 }
 
+/// Whether `name` matches the glob `pattern`, in which `*` matches any number of
+/// characters and `?` matches a single character.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+	let pattern: Vec<char> = pattern.chars().collect();
+	let name: Vec<char> = name.chars().collect();
+	let (mut pat_idx, mut name_idx) = (0, 0);
+	// Position of the last `*` and the position in `name` it was tried at.
+	let mut backtrack: Option<(usize, usize)> = None;
+
+	while name_idx < name.len() {
+		match pattern.get(pat_idx) {
+			Some('*') => {
+				backtrack = Some((pat_idx, name_idx));
+				pat_idx += 1;
+			}
+			Some(&ch) if ch == '?' || ch == name[name_idx] => {
+				pat_idx += 1;
+				name_idx += 1;
+			}
+			_ => {
+				// Let the last `*` match one more character.
+				let Some((star_idx, star_name_idx)) = backtrack else {
+					return false;
+				};
+				backtrack = Some((star_idx, star_name_idx + 1));
+				pat_idx = star_idx + 1;
+				name_idx = star_name_idx + 1;
+			}
+		}
+	}
+
+	pattern[pat_idx..].iter().all(|&ch| ch == '*')
+}
+
+/// What to remove from a value read from a file or a command.
 #[non_exhaustive]
-#[derive(Debug, Serialize, Deserialize, PartialEq)]
+#[derive(
+	Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Trim {
+	/// Keep the value as is.
+	None,
+	/// Remove a single trailing newline.
+	#[default]
+	Newline,
+	/// Remove all leading and trailing whitespace.
+	All,
+}
+
+impl Trim {
+	pub fn apply(self, val: &[u8]) -> &[u8] {
+		match self {
+			Self::None => val,
+			Self::Newline => val
+				.strip_suffix(b"\n")
+				.map_or(val, |val| val.strip_suffix(b"\r").unwrap_or(val)),
+			Self::All => val.trim_ascii(),
+		}
+	}
+}
+
+/// Either the path of the file or a table with more options.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum FromFile {
+	#[serde(with = "os_path")]
+	#[schemars(with = "OsValue")]
+	Path(path::PathBuf),
+	Table(FromFileTable),
+}
+
+impl<'de> Deserialize<'de> for FromFile {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(match plain_or_table(deserializer, OsValue::is_escaped)? {
+			PlainOrTable::Plain(OsValue(path)) => Self::Path(path.into()),
+			PlainOrTable::Table(table) => Self::Table(table),
+		})
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FromFileTable {
+	/// Path of the file, `~` is the home directory and relative paths are
+	/// relative to the environment's config directory.
+	#[serde(with = "os_path")]
+	#[schemars(with = "OsValue")]
+	pub path: path::PathBuf,
+
+	/// What to remove from the file's contents.
+	#[serde(default)]
+	pub trim: Trim,
+}
+
+impl FromFile {
+	pub fn table(&self) -> FromFileTable {
+		match self {
+			Self::Path(path) => FromFileTable {
+				path: path.clone(),
+				trim: Trim::default(),
+			},
+			Self::Table(table) => table.clone(),
+		}
+	}
+
+	/// Read the value for `var`, refusing to read files which are readable by
+	/// group or others.
+	pub fn read(
+		&self,
+		var: &str,
+		cfg_dir: &path::Path,
+	) -> Result<ffi::OsString> {
+		let table = self.table();
+		let file = files::resolve(&table.path, cfg_dir);
+
+		let meta = fs::metadata(&file).map_err(|err| {
+			Err::SecretFile(var.to_owned(), file.clone(), err)
+		})?;
+		let mode = os::unix::fs::PermissionsExt::mode(&meta.permissions());
+		if mode & 0o044 != 0 {
+			return Err(Err::SecretFilePerms(var.to_owned(), file, mode));
+		}
+
+		let val = fs::read(&file)
+			.map_err(|err| Err::SecretFile(var.to_owned(), file, err))?;
+
+		Ok(ffi::OsStr::from_bytes(table.trim.apply(&val)).to_owned())
+	}
+}
+
+/// Either the command or a table with more options.
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, JsonSchema, PartialEq)]
+#[serde(untagged)]
+pub enum FromCmd {
+	Cmd(String),
+	Table(FromCmdTable),
+}
+
+impl<'de> Deserialize<'de> for FromCmd {
+	fn deserialize<D>(deserializer: D) -> result::Result<Self, D::Error>
+	where
+		D: serde::Deserializer<'de>,
+	{
+		Ok(match plain_or_table(deserializer, |_| false)? {
+			PlainOrTable::Plain(cmd) => Self::Cmd(cmd),
+			PlainOrTable::Table(table) => Self::Table(table),
+		})
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct FromCmdTable {
+	/// Command run with `/bin/sh -c` in the environment's config directory.
+	pub cmd: String,
+
+	/// What to remove from the command's stdout.
+	#[serde(default)]
+	pub trim: Trim,
+
+	/// Seconds to wait for the command before failing, 0 waits indefinitely.
+	#[serde(default = "FromCmdTable::default_timeout")]
+	pub timeout: u64,
+}
+
+impl FromCmdTable {
+	fn default_timeout() -> u64 {
+		30
+	}
+}
+
+impl FromCmd {
+	pub fn table(&self) -> FromCmdTable {
+		match self {
+			Self::Cmd(cmd) => FromCmdTable {
+				cmd: cmd.clone(),
+				trim: Trim::default(),
+				timeout: FromCmdTable::default_timeout(),
+			},
+			Self::Table(table) => table.clone(),
+		}
+	}
+
+	/// Run the command for `var` and return its stdout.
+	pub fn read(
+		&self,
+		var: &str,
+		cfg_dir: &path::Path,
+	) -> Result<ffi::OsString> {
+		let table = self.table();
+		let (status, stdout) = run::output_timeout(
+			&table.cmd,
+			run::sh(&table.cmd).current_dir(cfg_dir),
+			time::Duration::from_secs(table.timeout),
+		)?;
+
+		if !status.success() {
+			return Err(Err::SecretCmd(var.to_owned(), table.cmd, status));
+		}
+
+		Ok(ffi::OsStr::from_bytes(table.trim.apply(&stdout)).to_owned())
+	}
+}
+
+#[non_exhaustive]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, PartialEq)]
 #[serde(default)]
+#[schemars(deny_unknown_fields)]
 pub struct Bin {
 	/// Whether to bring coreutils into the environment's path.
 	pub coreutils: bool,
 
 	/// Directories to add to PATH.
+	#[serde(with = "os_paths")]
+	#[schemars(with = "Vec<OsValue>")]
 	pub inherit_dirs: Vec<path::PathBuf>,
 
 	/// List of binaries to inherit from host, if the element starts with '/'
 	/// assume it's an absolute path for the binary, otherwise lookup the path
 	/// and use whatever is the result.
+	#[serde(with = "os_paths")]
+	#[schemars(with = "Vec<OsValue>")]
 	pub inherit: Vec<path::PathBuf>,
 
 	/// List of binaries to inherit from host, similar to `inherit` except that
@@ -248,10 +1806,10 @@ pub struct Bin {
 
 	/// Whether to exit if the binary is already symlinked for the environment
 	/// but points to a different file than what was symlinked to initially.
-	/// Only applies when the element in `self.inherit` isn't an absolute path.
+	/// Only applies when the element in `inherit` isn't an absolute path.
 	pub exit_on_change: bool,
 
-	/// Whether to exit if the element in `self.inherit` isn't an absolute path
+	/// Whether to exit if the element in `inherit` isn't an absolute path
 	/// and it wasn't found in PATH.
 	pub exit_on_not_found: bool,
 }
@@ -274,7 +1832,22 @@ impl Bin {
 		}
 	}
 
-	/// Inherit/Symlink binaries listed in `self.inherit` from the host.
+	/// Return `PATH` for the environment, `bin_dir` is only included if any
+	/// binaries are inherited.
+	pub fn path(&self, bin_dir: &path::Path) -> ffi::OsString {
+		let mut dirs = Vec::new();
+		if !self.inherit.is_empty()
+			|| !self.inherit_rename.is_empty()
+			|| self.coreutils
+		{
+			dirs.push(bin_dir.as_os_str());
+		}
+		dirs.extend(self.inherit_dirs.iter().map(|dir| dir.as_os_str()));
+
+		dirs.join(ffi::OsStr::new(":"))
+	}
+
+	/// Inherit/Symlink binaries listed in `inherit` from the host.
 	pub fn inherit_bins(&self, env_data_dir: &path::Path) -> Result<()> {
 		for host_bin in &self.inherit {
 			let host_bin_abs = match files::bin_get_abs(host_bin) {
@@ -489,9 +2062,32 @@ impl Bin {
 				}
 			}
 		} else {
-			dbgfmt!("Creating symlink {:?} from {:?}", target, src);
+			edbgfmt!("Creating symlink {:?} from {:?}", target, src);
 			os::unix::fs::symlink(src, target).dp()?;
 			Ok(())
 		}
 	}
 }
+
+// Byte range of the key at the dotted `path` in `doc`.
+fn key_span(
+	doc: &toml_edit::ImDocument<&str>,
+	path: &str,
+) -> Option<ops::Range<usize>> {
+	let mut item = doc.as_item();
+	let mut span = None;
+
+	for seg in path.split('.') {
+		let (key, next) = match item {
+			toml_edit::Item::Table(table) => table.get_key_value(seg)?,
+			toml_edit::Item::Value(toml_edit::Value::InlineTable(table)) => {
+				table.get_key_value(seg)?
+			}
+			_ => return span,
+		};
+		span = key.span().or(span);
+		item = next;
+	}
+
+	span
+}
@@ -0,0 +1,266 @@
+//! Trust store for the configs of project-local environments.
+//!
+//! A project's config can run commands through hooks, `vars.from_cmd` and its
+//! rc file, so it's only used once it's allowed with `cr allow`. The store is
+//! `trust.list` in the data home, with the hash of each allowed config next to
+//! its path, and a config has to be allowed again when it or a file it sources
+//! or writes into the environment changes.
+
+use std::ffi;
+use std::fs;
+use std::io;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::path;
+use std::result;
+
+use sha2::Digest;
+use thiserror::Error;
+
+use crate::debug::DebugPanic;
+use crate::files;
+use crate::senv;
+use crate::table;
+
+type Result<T> = result::Result<T, Err>;
+
+#[non_exhaustive]
+#[derive(Debug, Error)]
+pub enum Err {
+	#[error(transparent)]
+	IO(#[from] io::Error),
+	#[error(transparent)]
+	Files(#[from] files::Err),
+	#[error(transparent)]
+	Table(#[from] table::Err),
+	#[error(transparent)]
+	ShellEnv(#[from] senv::Err),
+
+	#[error(
+		"Config '{0}' isn't allowed, review it and run `cr allow {1}` to use \
+		 it"
+	)]
+	Untrusted(path::PathBuf, String),
+	#[error(
+		"Config '{0}' changed since it was allowed, review it and run `cr \
+		 allow {1}` to use it"
+	)]
+	Changed(path::PathBuf, String),
+}
+
+const STORE: &str = "trust.list";
+
+/// An allowed config.
+#[non_exhaustive]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Entry {
+	pub cfg_file: path::PathBuf,
+	/// Hex-encoded SHA-256 of the config and rc file when it was allowed.
+	pub hash: String,
+}
+
+/// Whether a config in the store is still the one which was allowed.
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum State {
+	Allowed,
+	Changed,
+	Missing,
+}
+
+impl State {
+	pub fn as_str(self) -> &'static str {
+		match self {
+			Self::Allowed => "allowed",
+			Self::Changed => "changed",
+			Self::Missing => "missing",
+		}
+	}
+}
+
+/// Deserialize the config of `shell_env`, returns an error if it's a
+/// project-local environment whose config isn't allowed or changed since it
+/// was. The config is read once, so the parsed contents are the hashed ones.
+pub fn load(
+	shell_env: &senv::Senv,
+	dirs: &xdg::BaseDirectories,
+	strict: bool,
+) -> Result<table::Root> {
+	if !shell_env.project {
+		return Ok(table::Root::from_senv(shell_env, strict)?);
+	}
+
+	let cfg_file = &shell_env.files.cfg_file;
+	let entries = read_store(dirs)?;
+	let Some(entry) = entries.iter().find(|entry| &entry.cfg_file == cfg_file)
+	else {
+		return Err(Err::Untrusted(cfg_file.clone(), shell_env.name.clone()));
+	};
+
+	let (env_table, hash) = read(shell_env, strict)?;
+	if entry.hash != hash {
+		return Err(Err::Changed(cfg_file.clone(), shell_env.name.clone()));
+	}
+
+	Ok(env_table)
+}
+
+/// Allow the current contents of the config of `shell_env`, and register the
+/// project.
+pub fn allow(
+	shell_env: &senv::Senv,
+	dirs: &xdg::BaseDirectories,
+) -> Result<()> {
+	let (_, hash) = read(shell_env, false)?;
+	shell_env.register()?;
+
+	let cfg_file = &shell_env.files.cfg_file;
+	let mut entries = read_store(dirs)?;
+	entries.retain(|entry| &entry.cfg_file != cfg_file);
+	entries.push(Entry {
+		cfg_file: cfg_file.clone(),
+		hash,
+	});
+	write_store(dirs, &entries)
+}
+
+/// Remove `cfg_file` from the store, returns false if it wasn't in it.
+pub fn deny(
+	cfg_file: &path::Path,
+	dirs: &xdg::BaseDirectories,
+) -> Result<bool> {
+	let mut entries = read_store(dirs)?;
+	let len = entries.len();
+	entries.retain(|entry| entry.cfg_file != cfg_file);
+	if entries.len() == len {
+		return Ok(false);
+	}
+	write_store(dirs, &entries)?;
+	Ok(true)
+}
+
+/// The allowed configs, sorted by path, with their current state.
+pub fn entries(dirs: &xdg::BaseDirectories) -> Result<Vec<(Entry, State)>> {
+	let mut entries = read_store(dirs)?;
+	entries.sort();
+
+	let mut states = Vec::new();
+	for entry in entries {
+		let state = if !entry.cfg_file.is_file() {
+			State::Missing
+		} else {
+			let hash = senv::Senv::new_project(&entry.cfg_file, dirs)
+				.ok()
+				.and_then(|shell_env| read(&shell_env, false).ok())
+				.map(|(_, hash)| hash);
+			if hash.as_ref() == Some(&entry.hash) {
+				State::Allowed
+			} else {
+				State::Changed
+			}
+		};
+		states.push((entry, state));
+	}
+
+	Ok(states)
+}
+
+// Deserialize the config and hash it together with the files it makes the
+// shell source or read variables from, which can run code as well: the rc
+// files, `shell.rc.source` and `vars.dotenv`. The sources of `template` and
+// `copy` in `[files]` are hashed too since they're written into the
+// environment, while `link` targets are expected to change.
+fn read(shell_env: &senv::Senv, strict: bool) -> Result<(table::Root, String)> {
+	let cfg_file = &shell_env.files.cfg_file;
+	let src = fs::read_to_string(cfg_file).dp()?;
+	let mut hasher = sha2::Sha256::new();
+	hasher.update(src.as_bytes());
+	let env_table = table::Root::from_src(cfg_file, src, strict)?;
+
+	let mut file_sources: Vec<_> = env_table
+		.files
+		.iter()
+		.filter_map(|(file, source)| match source {
+			table::FileSource::Template(src) | table::FileSource::Copy(src) => {
+				Some((file, src))
+			}
+			_ => None,
+		})
+		.collect();
+	file_sources.sort();
+
+	let rc_file = &shell_env.files.rc_file;
+	let cfg_dir = &shell_env.files.cfg_dir;
+	let sourced = [rc_file.clone(), rc_file.with_extension("fish")]
+		.into_iter()
+		.chain(
+			env_table
+				.shell
+				.rc
+				.source
+				.iter()
+				.chain(&env_table.vars.dotenv)
+				.chain(file_sources.into_iter().map(|(_, src)| src))
+				.map(|file| files::resolve(file, cfg_dir)),
+		);
+	for file in sourced {
+		// Lengths are included so that moving bytes between the path and the
+		// contents changes the hash.
+		let path = file.as_os_str().as_bytes();
+		hasher.update(path.len().to_le_bytes());
+		hasher.update(path);
+		match fs::read(&file) {
+			Ok(contents) => {
+				hasher.update(contents.len().to_le_bytes());
+				hasher.update(contents);
+			}
+			Err(err) if err.kind() == io::ErrorKind::NotFound => {
+				hasher.update(u64::MAX.to_le_bytes());
+			}
+			Err(err) => return Err(Err::IO(err)).dp(),
+		}
+	}
+
+	let hash = hasher
+		.finalize()
+		.iter()
+		.map(|byte| format!("{byte:02x}"))
+		.collect();
+	Ok((env_table, hash))
+}
+
+// Each entry is the hash and the path separated by a space, and entries are
+// separated by NUL since paths can contain newlines.
+fn read_store(dirs: &xdg::BaseDirectories) -> Result<Vec<Entry>> {
+	let contents = match fs::read(dirs.get_data_home().join(STORE)) {
+		Ok(contents) => contents,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => Vec::new(),
+		Err(err) => return Err(Err::IO(err)).dp(),
+	};
+
+	Ok(contents
+		.split(|&byte| byte == 0)
+		.filter_map(|entry| {
+			let (hash, cfg_file) = entry.split_at_checked(64)?;
+			Some(Entry {
+				cfg_file: ffi::OsString::from_vec(cfg_file.get(1..)?.to_vec())
+					.into(),
+				hash: String::from_utf8(hash.to_vec()).ok()?,
+			})
+		})
+		.collect())
+}
+
+fn write_store(dirs: &xdg::BaseDirectories, entries: &[Entry]) -> Result<()> {
+	let mut contents = Vec::new();
+	for entry in entries {
+		contents.extend_from_slice(entry.hash.as_bytes());
+		contents.push(b' ');
+		contents.extend_from_slice(entry.cfg_file.as_os_str().as_bytes());
+		contents.push(0);
+	}
+
+	let data_home = dirs.get_data_home();
+	fs::create_dir_all(&data_home).dp()?;
+	files::write_atomic(&data_home.join(STORE), contents)?;
+	Ok(())
+}
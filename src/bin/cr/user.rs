@@ -0,0 +1,58 @@
+//! Information about the user running cleanroom from the passwd database.
+
+use std::ffi;
+use std::os::unix::ffi::OsStrExt;
+use std::path;
+use std::ptr;
+
+/// The passwd entry of a user.
+#[non_exhaustive]
+#[derive(Debug, Clone)]
+pub struct Passwd {
+	pub name: ffi::OsString,
+	pub home: path::PathBuf,
+}
+
+/// The passwd entry of the real user ID of the process, `None` if it doesn't
+/// have one.
+pub fn current() -> Option<Passwd> {
+	// SAFETY: `getuid` can't fail.
+	let uid = unsafe { libc::getuid() };
+	let mut buf = vec![0_u8; 1024];
+
+	loop {
+		// SAFETY: All-zero is a valid value for `libc::passwd`.
+		let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+		let mut res: *mut libc::passwd = ptr::null_mut();
+		// SAFETY: The pointers are valid for the duration of the call and the
+		// length is the size of `buf`.
+		let err = unsafe {
+			libc::getpwuid_r(
+				uid,
+				&mut pwd,
+				buf.as_mut_ptr().cast(),
+				buf.len(),
+				&mut res,
+			)
+		};
+
+		if err == libc::ERANGE && buf.len() < 1 << 20 {
+			buf.resize(buf.len() * 2, 0);
+			continue;
+		}
+		if err != 0 || res.is_null() {
+			return None;
+		}
+
+		// SAFETY: On success the fields point to NUL-terminated strings in
+		// `buf`, which are copied before `buf` is dropped.
+		let field = |ptr: *const libc::c_char| unsafe {
+			ffi::OsStr::from_bytes(ffi::CStr::from_ptr(ptr).to_bytes())
+				.to_owned()
+		};
+		return Some(Passwd {
+			name: field(pwd.pw_name),
+			home: field(pwd.pw_dir).into(),
+		});
+	}
+}
@@ -0,0 +1,146 @@
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn valid() {
+	let xdg = Xdg::new();
+	xdg.new_env("valid");
+
+	let output = xdg.cmd().args(["check", "valid"]).output().unwrap();
+	let stdout = std::str::from_utf8(&output.stdout).unwrap();
+
+	assert!(output.status.success());
+	assert!(stdout.starts_with("valid: 0 error(s)"));
+}
+
+#[test]
+fn missing_var() {
+	let xdg = Xdg::new();
+	xdg.new_env("missing_var");
+	fs::write(
+		xdg.cfg_file("missing_var"),
+		"[vars]\ninherit = [\"CR_TEST_MISSING\"]\n",
+	)
+	.unwrap();
+
+	let output = xdg.cmd().args(["check", "missing_var"]).output().unwrap();
+	let stdout = std::str::from_utf8(&output.stdout).unwrap();
+
+	assert!(!output.status.success());
+	assert!(stdout.contains("error[vars]"));
+	assert!(stdout.contains("CR_TEST_MISSING"));
+}
+
+#[test]
+fn missing_var_set() {
+	// `set` overrides a missing inherited variable in both `check` and `use`.
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"missing_var_set",
+		"[vars]\ninherit = [\"CR_TEST_MISSING\"]\n\
+		 set = { CR_TEST_MISSING = \"set\" }",
+	);
+
+	let output = xdg
+		.cmd()
+		.args(["check", "missing_var_set"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+
+	let output =
+		xdg.use_env(&mut xdg.cmd(), "missing_var_set", "echo $CR_TEST_MISSING");
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nset\n"));
+}
+
+#[test]
+fn unknown_key() {
+	let xdg = Xdg::new();
+	xdg.new_env("unknown_key");
+	fs::write(xdg.cfg_file("unknown_key"), "[vars]\ninherrit = []\n").unwrap();
+
+	let output = xdg.cmd().args(["check", "unknown_key"]).output().unwrap();
+	let stdout = std::str::from_utf8(&output.stdout).unwrap();
+	assert!(output.status.success());
+	assert!(stdout.contains("warning[config]: Unknown key 'vars.inherrit'"));
+
+	let output = xdg
+		.cmd()
+		.args(["check", "--deny-warnings", "unknown_key"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+}
+
+#[test]
+fn strict() {
+	let xdg = Xdg::new();
+	xdg.new_env("strict");
+	fs::write(
+		xdg.cfg_file("strict"),
+		"version = 1\n[shell]\ninteractve = true\n",
+	)
+	.unwrap();
+
+	let output = xdg
+		.cmd()
+		.args(["--strict", "check", "strict"])
+		.output()
+		.unwrap();
+	let stdout = std::str::from_utf8(&output.stdout).unwrap();
+
+	assert!(!output.status.success());
+	assert!(stdout.contains("did you mean 'interactive'?"));
+	assert!(stdout.contains("config.toml:3:1)"));
+}
+
+#[test]
+fn unknown_key_in_variant() {
+	// Tables which can also be a plain value reject unknown keys.
+	let xdg = Xdg::new();
+	xdg.new_env("unknown_key_in_variant");
+	let secret = xdg.root.join("secret");
+	fs::write(&secret, "x").unwrap();
+	fs::set_permissions(&secret, fs::Permissions::from_mode(0o600)).unwrap();
+	for (cfg, key) in [
+		(
+			format!(
+				"[vars.from_file]\nX = {{ path = \"{}\", trimm = \"all\" }}\n",
+				secret.display()
+			)
+			.as_str(),
+			"trimm",
+		),
+		(
+			"[vars.from_cmd]\nX = { cmd = \"true\", timout = 1 }\n",
+			"timout",
+		),
+		(
+			"[vars]\ninherit = [{ name = \"X\", defualt = \"y\" }]\n",
+			"defualt",
+		),
+		(
+			"[vars.prepend]\nX = { entries = [], seperator = \",\" }\n",
+			"seperator",
+		),
+		("[vars.set]\nX = { escaped = \"y\", typo = 1 }\n", "typo"),
+		(
+			"[hooks]\npre_enter = [{ cmd = \"true\", on_fail = \"warn\" }]\n",
+			"on_fail",
+		),
+	] {
+		fs::write(xdg.cfg_file("unknown_key_in_variant"), cfg).unwrap();
+		let output = xdg
+			.cmd()
+			.args(["check", "unknown_key_in_variant"])
+			.output()
+			.unwrap();
+		assert!(!output.status.success(), "{cfg}");
+		let out = String::from_utf8_lossy(&output.stdout)
+			+ String::from_utf8_lossy(&output.stderr);
+		assert!(out.contains(&format!("unknown field `{key}`")), "{out}");
+	}
+}
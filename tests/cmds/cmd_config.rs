@@ -0,0 +1,79 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn set_keeps_comments() {
+	let xdg = Xdg::new();
+	xdg.new_env("set");
+	let cfg_file = xdg.cfg_file("set");
+	fs::write(
+		&cfg_file,
+		"version = 1\n# Shell\n[shell]\nbin = \"/bin/sh\"\n",
+	)
+	.unwrap();
+
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "set", "shell.bin", "/bin/bash"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	assert_eq!(
+		fs::read_to_string(&cfg_file).unwrap(),
+		"version = 1\n# Shell\n[shell]\nbin = \"/bin/bash\"\n"
+	);
+
+	let output = xdg
+		.cmd()
+		.args(["config", "get", "set", "shell.bin"])
+		.output()
+		.unwrap();
+	assert_eq!(std::str::from_utf8(&output.stdout).unwrap(), "/bin/bash\n");
+}
+
+#[test]
+fn append_unset() {
+	let xdg = Xdg::new();
+	xdg.new_env("append");
+	let cfg_file = xdg.cfg_file("append");
+	fs::write(&cfg_file, "version = 1\n").unwrap();
+
+	for args in [
+		["append", "append", "vars.inherit", "HOME"],
+		["append", "append", "vars.inherit", "TERM"],
+		["set", "append", "vars.set.EDITOR", "vi"],
+	] {
+		let status = xdg.cmd().arg("config").args(args).status().unwrap();
+		assert!(status.success());
+	}
+	let status = xdg
+		.cmd()
+		.args(["config", "unset", "append", "vars.set.EDITOR"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	assert_eq!(
+		fs::read_to_string(&cfg_file).unwrap(),
+		"version = 1\n\n[vars]\ninherit = [\"HOME\", \"TERM\"]\n\n[vars.set]\n"
+	);
+}
+
+#[test]
+fn invalid() {
+	let xdg = Xdg::new();
+	xdg.new_env("invalid");
+	let cfg_file = xdg.cfg_file("invalid");
+	let orig = fs::read_to_string(&cfg_file).unwrap();
+
+	for args in [
+		["set", "invalid", "shell.login", "yes"],
+		["set", "invalid", "shell.logn", "true"],
+	] {
+		let output = xdg.cmd().arg("config").args(args).output().unwrap();
+		assert!(!output.status.success());
+	}
+
+	assert_eq!(fs::read_to_string(&cfg_file).unwrap(), orig);
+}
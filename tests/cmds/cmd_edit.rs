@@ -0,0 +1,79 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn valid() {
+	let xdg = Xdg::new();
+	xdg.new_env("valid");
+	let cfg_file = xdg.cfg_file("valid");
+
+	let status = xdg
+		.cmd()
+		.env("PATH", "/bin:/usr/bin")
+		.env("EDITOR", "sed -i s@/bin/sh@/bin/bash@")
+		.args(["edit", "valid"])
+		.status()
+		.unwrap();
+
+	assert!(status.success());
+	assert!(fs::read_to_string(&cfg_file)
+		.unwrap()
+		.contains("bin = \"/bin/bash\""));
+	// Only config.toml and rc.sh, the temporary copy was removed.
+	assert_eq!(fs::read_dir(cfg_file.parent().unwrap()).unwrap().count(), 2);
+}
+
+#[test]
+fn invalid() {
+	let xdg = Xdg::new();
+	xdg.new_env("invalid");
+	let cfg_file = xdg.cfg_file("invalid");
+	let orig = fs::read_to_string(&cfg_file).unwrap();
+
+	// The second run of the editor doesn't change anything so the edit is
+	// aborted.
+	let output = xdg
+		.cmd()
+		.env("PATH", "/bin:/usr/bin")
+		.env("EDITOR", "sed -i s/^login\\ =\\ false/login\\ =\\ 1/")
+		.args(["edit", "invalid"])
+		.output()
+		.unwrap();
+	let stderr = std::str::from_utf8(&output.stderr).unwrap();
+
+	assert!(!output.status.success());
+	assert!(stderr.contains("expected a boolean"));
+	assert_eq!(fs::read_to_string(&cfg_file).unwrap(), orig);
+}
+
+#[test]
+fn error_lines() {
+	let xdg = Xdg::new();
+	xdg.new_env("error_lines");
+	let cfg_file = xdg.cfg_file("error_lines");
+	let orig = fs::read_to_string(&cfg_file).unwrap();
+	let log = xdg.root.join("log");
+
+	// The error is appended to the copy so its line numbers stay the same.
+	let editor = format!(
+		"f() {{ cat \"$1\" >> '{}'; \
+		 sed -i s/^login\\ =\\ false/login\\ =\\ 1/ \"$1\"; }}; f",
+		log.display()
+	);
+	let output = xdg
+		.cmd()
+		.env("PATH", "/bin:/usr/bin")
+		.env("EDITOR", editor)
+		.args(["edit", "error_lines"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+
+	let invalid = orig.replace("login = false", "login = 1");
+	let log = fs::read_to_string(log).unwrap();
+	let second = log.strip_prefix(&orig).unwrap();
+	let errors = second.strip_prefix(&invalid).unwrap();
+	assert!(!errors.is_empty());
+	assert!(errors.lines().all(|line| line.starts_with("# cr: ")));
+}
@@ -0,0 +1,85 @@
+use std::process;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn formats() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"formats",
+		"[vars]\nset = { FOO = \"it's\" }\ncleanroom_vars = false",
+	);
+	let env = |format: &str| {
+		let output = xdg
+			.cmd()
+			.args(["env", "formats", "--format", format])
+			.output()
+			.unwrap();
+		assert!(output.status.success());
+		output.stdout
+	};
+	let path = "/usr/local/bin:/bin:/usr/bin";
+
+	assert_eq!(
+		env("posix"),
+		format!("export FOO='it'\\''s'\nexport PATH='{path}'\n").as_bytes()
+	);
+	assert_eq!(
+		env("fish"),
+		b"set -gx FOO 'it\\'s'\nset -gx PATH '/usr/local/bin' '/bin' '/usr/bin'\n"
+	);
+	assert_eq!(env("nul"), format!("FOO=it's\0PATH={path}\0").as_bytes());
+	let json: serde_json::Value = serde_json::from_slice(&env("json")).unwrap();
+	assert_eq!(json, serde_json::json!({ "FOO": "it's", "PATH": path }));
+
+	// The output can be evaluated by the shell.
+	let output = process::Command::new("/bin/sh")
+		.args(["-c", "eval \"$1\"; echo \"$FOO\""])
+		.arg("sh")
+		.arg(String::from_utf8(env("posix")).unwrap())
+		.output()
+		.unwrap();
+	assert_eq!(output.stdout, b"it's\n");
+}
+
+#[test]
+fn deny() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"deny",
+		"[vars]\ndeny = [\"CR_T_DENY*\"]\ncleanroom_vars = false",
+	);
+	let status = xdg
+		.cmd()
+		.args(["config", "append", "deny", "bin.inherit", "/bin/sh"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	// Denied variables of the calling shell are unset, and nothing but the
+	// variables is printed.
+	let env = |format: &str| {
+		let output = xdg
+			.cmd()
+			.env("CR_T_DENY_A", "a")
+			.args(["env", "deny", "--format", format])
+			.output()
+			.unwrap();
+		assert!(output.status.success());
+		String::from_utf8(output.stdout).unwrap()
+	};
+	let path = xdg.data_home.join("cleanroom/deny/bin");
+	let path = format!("{}:/usr/local/bin:/bin:/usr/bin", path.display());
+
+	assert_eq!(
+		env("posix"),
+		format!("unset CR_T_DENY_A\nexport PATH='{path}'\n")
+	);
+	assert!(env("fish").starts_with("set -e CR_T_DENY_A\nset -gx PATH '"));
+	assert_eq!(env("nul"), format!("PATH={path}\0"));
+	let json: serde_json::Value = serde_json::from_str(&env("json")).unwrap();
+	assert_eq!(
+		json,
+		serde_json::json!({ "CR_T_DENY_A": null, "PATH": path })
+	);
+}
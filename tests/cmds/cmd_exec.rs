@@ -0,0 +1,121 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+const PROJECT_CFG: &str = "version = 1\n[vars]\nset = { FOO = \"bar\" }\n\
+						   [shell]\nbin = \"/bin/bash\"\n\
+						   interactive = false\n[bin]\ncoreutils = false\n";
+
+#[test]
+fn exit_code() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("exit_code", "[vars]\nset = { FOO = \"bar\" }");
+
+	let output = xdg
+		.cmd()
+		.args(["exec", "exit_code", "--", "/bin/sh", "-c", "echo $FOO"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nbar\n"));
+
+	let output = xdg
+		.cmd()
+		.args(["exec", "exit_code", "--", "/bin/sh", "-c", "exit 3"])
+		.output()
+		.unwrap();
+	assert_eq!(output.status.code(), Some(3));
+}
+
+#[test]
+fn project() {
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	let sub_dir = project_dir.join("sub");
+	fs::create_dir_all(&sub_dir).unwrap();
+	fs::write(project_dir.join(".cleanroom.toml"), PROJECT_CFG).unwrap();
+	xdg.allow(&sub_dir);
+
+	// Found from a subdirectory.
+	let output = xdg
+		.cmd()
+		.current_dir(&sub_dir)
+		.args(["exec", ".", "--", "/bin/sh", "-c", "echo $FOO"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nbar\n"));
+
+	let output = xdg.use_env(xdg.cmd().current_dir(&sub_dir), ".", "echo $FOO");
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nbar\n"));
+
+	// Its data dir is outside of the project.
+	let projects_dir = xdg.data_home.join("cleanroom/.projects");
+	assert_eq!(fs::read_dir(&projects_dir).unwrap().count(), 1);
+
+	let output = xdg.cmd().args(["ls"]).output().unwrap();
+	assert!(output.status.success());
+	let project_dir = fs::canonicalize(&project_dir).unwrap();
+	assert_eq!(
+		output.stdout,
+		format!("{}\n", project_dir.display()).as_bytes()
+	);
+
+	// No longer listed once the config is gone.
+	fs::remove_file(project_dir.join(".cleanroom.toml")).unwrap();
+	let output = xdg.cmd().args(["ls"]).output().unwrap();
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"");
+}
+
+#[test]
+fn project_dir() {
+	let xdg = Xdg::new();
+	let cfg_dir = xdg.root.join("project/.cleanroom");
+	fs::create_dir_all(&cfg_dir).unwrap();
+	fs::write(cfg_dir.join("config.toml"), PROJECT_CFG).unwrap();
+	xdg.allow(&cfg_dir);
+
+	let output = xdg
+		.cmd()
+		.args(["exec"])
+		.arg(xdg.root.join("project"))
+		.args(["--", "/bin/sh", "-c", "echo $FOO"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nbar\n"));
+
+	let output = xdg
+		.cmd()
+		.current_dir(&xdg.root)
+		.args(["exec", ".", "--", "/bin/true"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+}
+
+#[test]
+fn unused_project() {
+	// Commands which only read the config work before the project is used.
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(&project_dir).unwrap();
+	fs::write(project_dir.join(".cleanroom.toml"), PROJECT_CFG).unwrap();
+
+	for args in [
+		&["config", "get", ".", "shell.bin"][..],
+		&["home", "."],
+		&["check", "."],
+	] {
+		let output = xdg
+			.cmd()
+			.current_dir(&project_dir)
+			.args(args)
+			.output()
+			.unwrap();
+		assert!(output.status.success(), "{args:?}");
+	}
+	assert!(!xdg.data_home.join("cleanroom/.projects").exists());
+}
@@ -0,0 +1,71 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn isolated() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("hist", "");
+
+	// Shared by default.
+	let output = xdg.cmd().args(["history", "hist"]).output().unwrap();
+	assert!(!output.status.success());
+
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "hist", "shell.history", "isolated"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	let output =
+		xdg.use_env(&mut xdg.cmd(), "hist", "echo 'ls -a' >> \"$HISTFILE\"");
+	assert!(output.status.success());
+	assert!(xdg.data_home.join("cleanroom/hist/history").is_file());
+
+	let output = xdg.cmd().args(["history", "hist"]).output().unwrap();
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"ls -a\n");
+
+	let output = xdg
+		.cmd()
+		.args(["history", "--clear", "hist"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert!(!xdg.data_home.join("cleanroom/hist/history").exists());
+}
+
+#[test]
+fn fish() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("fish-hist", "");
+	for (key, val) in [
+		("shell.bin", "/usr/bin/fish"),
+		("shell.history", "isolated"),
+	] {
+		let status = xdg
+			.cmd()
+			.args(["config", "set", "fish-hist", key, val])
+			.status()
+			.unwrap();
+		assert!(status.success());
+	}
+
+	// fish saves the history of its session in its own data directory.
+	let history_file = xdg.data_home.join("fish/cleanroom_fish_hist_history");
+	fs::create_dir_all(history_file.parent().unwrap()).unwrap();
+	fs::write(&history_file, "- cmd: ls -a\n").unwrap();
+
+	let output = xdg.cmd().args(["history", "fish-hist"]).output().unwrap();
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"- cmd: ls -a\n");
+
+	let output = xdg
+		.cmd()
+		.args(["history", "--clear", "fish-hist"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert!(!history_file.exists());
+}
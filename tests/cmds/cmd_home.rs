@@ -0,0 +1,32 @@
+use crate::common::env::Xdg;
+
+#[test]
+fn private() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("private", "");
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "private", "shell.home", "private"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	let home_dir = xdg.data_home.join("cleanroom/private/home");
+	let output = xdg.cmd().args(["home", "private"]).output().unwrap();
+	assert!(output.status.success());
+	assert_eq!(
+		output.stdout,
+		format!("{}\n", home_dir.display()).as_bytes()
+	);
+
+	let output = xdg.use_env(
+		&mut xdg.cmd(),
+		"private",
+		"printf '%s|' \"$HOME\" \"$XDG_CACHE_HOME\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with(&format!("\n{0}|{0}/.cache|", home_dir.display())));
+	assert!(home_dir.join(".local/state").is_dir());
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path;
+use std::process;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn bash() {
+	round_trip("bash", |hook| {
+		format!(
+			"{hook}\nexport FOO=orig PATH=/bin CR_T_DENY_A=a\n\
+			 cd project/sub; _cleanroom_hook\n\
+			 echo \"$FOO ${{CR_T_DENY_A-unset}} $CLEANROOM_ENV\"\n\
+			 cd ../../mapped; _cleanroom_hook\n\
+			 echo \"$FOO ${{CR_T_DENY_A-unset}} $CLEANROOM_ENV\"\n\
+			 cd ..; _cleanroom_hook\n\
+			 echo \"$FOO $CR_T_DENY_A $PATH ${{CLEANROOM_ENV-unset}}\"\n"
+		)
+	});
+}
+
+#[test]
+fn zsh() {
+	round_trip("zsh", |hook| {
+		format!(
+			"{hook}\nexport FOO=orig PATH=/bin CR_T_DENY_A=a\n\
+			 cd project/sub; _cleanroom_hook\n\
+			 echo \"$FOO ${{CR_T_DENY_A-unset}} $CLEANROOM_ENV\"\n\
+			 cd ../../mapped; _cleanroom_hook\n\
+			 echo \"$FOO ${{CR_T_DENY_A-unset}} $CLEANROOM_ENV\"\n\
+			 cd ..; _cleanroom_hook\n\
+			 echo \"$FOO $CR_T_DENY_A $PATH ${{CLEANROOM_ENV-unset}}\"\n"
+		)
+	});
+}
+
+#[test]
+fn fish() {
+	round_trip("fish", |hook| {
+		format!(
+			"{hook}\nfunction val\n\
+			 \tset -q $argv[1]; and echo $$argv[1]; or echo unset\nend\n\
+			 set -gx FOO orig; set -gx PATH /bin; set -gx CR_T_DENY_A a\n\
+			 cd project/sub; __cleanroom_hook\n\
+			 echo (val FOO) (val CR_T_DENY_A) (val CLEANROOM_ENV)\n\
+			 cd ../../mapped; __cleanroom_hook\n\
+			 echo (val FOO) (val CR_T_DENY_A) (val CLEANROOM_ENV)\n\
+			 cd ..; __cleanroom_hook\n\
+			 echo (val FOO) (val CR_T_DENY_A) $PATH (val CLEANROOM_ENV)\n"
+		)
+	});
+}
+
+// Runs `script` with the hook snippet of `shell`, entering a project, an
+// environment mapped with `shell.cwd` and leaving both. Skipped if the shell
+// isn't installed.
+fn round_trip<F>(shell: &str, script: F)
+where
+	F: Fn(&str) -> String,
+{
+	let Some(bin) = ["/bin", "/usr/bin", "/usr/local/bin"]
+		.iter()
+		.map(|dir| path::Path::new(dir).join(shell))
+		.find(|bin| bin.is_file())
+	else {
+		eprintln!("Skipping, {shell} isn't installed");
+		return;
+	};
+
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(project_dir.join("sub")).unwrap();
+	fs::write(
+		project_dir.join(".cleanroom.toml"),
+		"version = 1\n[vars]\nset = { FOO = \"bar\" }\n\
+		 deny = [\"CR_T_DENY*\"]\n[bin]\ncoreutils = false\n",
+	)
+	.unwrap();
+	xdg.allow(&project_dir);
+	xdg.new_script_env("mapped", "");
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "mapped", "shell.cwd"])
+		.arg(xdg.root.join("mapped"))
+		.status()
+		.unwrap();
+	assert!(status.success());
+	fs::create_dir_all(xdg.root.join("mapped")).unwrap();
+
+	let output = xdg.cmd().args(["hook", shell]).output().unwrap();
+	assert!(output.status.success());
+	let hook = String::from_utf8(output.stdout).unwrap();
+
+	let output = process::Command::new(bin)
+		.env_clear()
+		.env("HOME", &xdg.root)
+		.env("XDG_CONFIG_HOME", &xdg.cfg_home)
+		.env("XDG_DATA_HOME", &xdg.data_home)
+		.current_dir(&xdg.root)
+		.args(["-c", &script(&hook)])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	let project_dir = fs::canonicalize(&project_dir).unwrap();
+	assert_eq!(
+		std::str::from_utf8(&output.stdout).unwrap(),
+		format!(
+			"bar unset {}\norig a mapped\norig a /bin unset\n",
+			project_dir.display()
+		)
+	);
+}
+
+#[test]
+fn untrusted() {
+	// Changing into an untrusted project doesn't register it.
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(&project_dir).unwrap();
+	let src = "[vars]\nset = { FOO = \"bar\" }\n";
+	fs::write(project_dir.join(".cleanroom.toml"), src).unwrap();
+
+	let output = xdg
+		.cmd()
+		.current_dir(&project_dir)
+		.args(["hook", "bash", "--apply"])
+		.output()
+		.unwrap();
+	assert!(!output.status.success());
+	assert!(!std::str::from_utf8(&output.stdout).unwrap().contains("FOO"));
+
+	let output = xdg.cmd().args(["ls"]).output().unwrap();
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"");
+	assert!(!xdg.data_home.join("cleanroom/.projects").exists());
+	assert_eq!(
+		fs::read_to_string(project_dir.join(".cleanroom.toml")).unwrap(),
+		src
+	);
+}
+
+#[test]
+fn quiet() {
+	// Named environments which don't match aren't warned about at every
+	// prompt.
+	let xdg = Xdg::new();
+	xdg.new_env("typo");
+	fs::write(xdg.cfg_file("typo"), "version = 1\n[shell]\ntypo = 1\n")
+		.unwrap();
+	xdg.new_env("old");
+	fs::write(xdg.cfg_file("old"), "[shell]\nbin = \"/bin/bash\"\n").unwrap();
+
+	let output = xdg
+		.cmd()
+		.current_dir(&xdg.root)
+		.args(["hook", "bash", "--apply"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+	assert_eq!(output.stderr, b"");
+}
@@ -0,0 +1,87 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+static V0: &str = "# Comment\n[vars]\ninherit = [\"HOME\"]\n";
+
+#[test]
+fn dry_run() {
+	let xdg = Xdg::new();
+	xdg.new_env("dry_run");
+	fs::write(xdg.cfg_file("dry_run"), V0).unwrap();
+
+	let output = xdg.cmd().args(["migrate", "-n"]).output().unwrap();
+	let stdout = std::str::from_utf8(&output.stdout).unwrap();
+
+	assert!(output.status.success());
+	assert!(stdout.starts_with("dry_run: version 0 -> 1 (dry run)\n"));
+	assert_eq!(fs::read_to_string(xdg.cfg_file("dry_run")).unwrap(), V0);
+}
+
+#[test]
+fn migrate() {
+	let xdg = Xdg::new();
+	xdg.new_env("migrate");
+	let cfg_file = xdg.cfg_file("migrate");
+	fs::write(&cfg_file, V0).unwrap();
+
+	let output = xdg.cmd().args(["migrate", "migrate"]).output().unwrap();
+	assert!(output.status.success());
+
+	let migrated = fs::read_to_string(&cfg_file).unwrap();
+	assert!(migrated.starts_with("version = 1\n"));
+	assert!(migrated.contains("# Comment\n[vars]"));
+	assert_eq!(
+		fs::read_to_string(cfg_file.with_extension("toml.v0.bak")).unwrap(),
+		V0
+	);
+
+	let output = xdg.cmd().args(["migrate", "migrate"]).output().unwrap();
+	let stdout = std::str::from_utf8(&output.stdout).unwrap();
+	assert_eq!(stdout, "migrate: up to date (version 1)\n");
+}
+
+#[test]
+fn in_memory() {
+	let xdg = Xdg::new();
+	xdg.new_env("in_memory");
+	let cfg_file = xdg.cfg_file("in_memory");
+	fs::write(&cfg_file, V0).unwrap();
+
+	// Loading an older config doesn't rewrite it.
+	let output = xdg.cmd().args(["ls"]).output().unwrap();
+	assert!(output.status.success());
+	assert_eq!(output.stdout, b"in_memory\n");
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("run `cr migrate`"));
+	assert_eq!(fs::read_to_string(&cfg_file).unwrap(), V0);
+	assert!(!cfg_file.with_extension("toml.v0.bak").exists());
+}
+
+#[test]
+fn keep_backups() {
+	let xdg = Xdg::new();
+	xdg.new_env("keep_backups");
+	let cfg_file = xdg.cfg_file("keep_backups");
+	let v0_other = "[vars]\ninherit = [\"USER\"]\n";
+
+	for src in [V0, v0_other] {
+		fs::write(&cfg_file, src).unwrap();
+		let status = xdg
+			.cmd()
+			.args(["migrate", "keep_backups"])
+			.status()
+			.unwrap();
+		assert!(status.success());
+	}
+
+	assert_eq!(
+		fs::read_to_string(cfg_file.with_extension("toml.v0.bak")).unwrap(),
+		V0
+	);
+	assert_eq!(
+		fs::read_to_string(cfg_file.with_extension("toml.v0.1.bak")).unwrap(),
+		v0_other
+	);
+}
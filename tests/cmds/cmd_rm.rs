@@ -0,0 +1,64 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn named() {
+	let xdg = Xdg::new();
+	xdg.new_env("named");
+	let status = xdg.cmd().args(["rm", "named"]).status().unwrap();
+	assert!(status.success());
+	assert!(!xdg.cfg_home.join("cleanroom/named").exists());
+	assert!(!xdg.data_home.join("cleanroom/named").exists());
+}
+
+#[test]
+fn invalid_name() {
+	let xdg = Xdg::new();
+	xdg.new_env("keep");
+	for name in ["..", ""] {
+		let status = xdg.cmd().args(["rm", name]).status().unwrap();
+		assert!(!status.success());
+	}
+	assert!(xdg.cfg_file("keep").is_file());
+}
+
+#[test]
+fn project() {
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(&project_dir).unwrap();
+	fs::write(project_dir.join(".cleanroom.toml"), "version = 1\n").unwrap();
+	fs::write(project_dir.join("src.rs"), "").unwrap();
+	xdg.allow(&project_dir);
+	xdg.new_env("keep");
+
+	// Removes the project as printed by `cr ls`, or found from a directory in
+	// it, without touching the checkout.
+	let project_dir = fs::canonicalize(&project_dir).unwrap();
+	let output = xdg.cmd().args(["ls"]).output().unwrap();
+	let name = format!("{}", project_dir.display());
+	assert_eq!(output.stdout, format!("{name}\nkeep\n").as_bytes());
+
+	let status = xdg.cmd().args(["rm", &name]).status().unwrap();
+	assert!(status.success());
+	assert!(project_dir.join(".cleanroom.toml").is_file());
+	assert!(project_dir.join("src.rs").is_file());
+	assert!(xdg.cfg_file("keep").is_file());
+	let output = xdg.cmd().args(["ls"]).output().unwrap();
+	assert_eq!(output.stdout, b"keep\n");
+	let output = xdg.cmd().args(["trust", "ls"]).output().unwrap();
+	assert_eq!(output.stdout, b"");
+
+	xdg.allow(&project_dir);
+
+	let status = xdg
+		.cmd()
+		.current_dir(&project_dir)
+		.args(["rm", "."])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	assert!(project_dir.join("src.rs").is_file());
+	assert!(xdg.cfg_file("keep").is_file());
+}
@@ -0,0 +1,18 @@
+use crate::common::env::Xdg;
+
+#[test]
+fn schema() {
+	let xdg = Xdg::new();
+
+	let output = xdg.cmd().args(["schema"]).output().unwrap();
+	assert!(output.status.success());
+
+	let schema: serde_json::Value =
+		serde_json::from_slice(&output.stdout).unwrap();
+	let shell = &schema["definitions"]["Shell"];
+
+	assert_eq!(shell["additionalProperties"], false);
+	assert_eq!(shell["properties"]["bin"]["default"], "/bin/sh");
+	assert!(shell["properties"]["bin"]["description"].is_string());
+	assert!(schema["properties"]["version"].is_object());
+}
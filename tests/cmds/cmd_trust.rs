@@ -0,0 +1,155 @@
+use std::fs;
+
+use crate::common::env::Xdg;
+
+#[test]
+fn allow_deny() {
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(&project_dir).unwrap();
+	let cfg_file = project_dir.join(".cleanroom.toml");
+	fs::write(
+		&cfg_file,
+		"version = 1\n[shell]\nbin = \"/bin/bash\"\ninteractive = false\n\
+		 [bin]\ncoreutils = false\n",
+	)
+	.unwrap();
+	let cfg_file = fs::canonicalize(&cfg_file).unwrap();
+	let exec = || {
+		xdg.cmd()
+			.current_dir(&project_dir)
+			.args(["exec", ".", "--", "/bin/true"])
+			.output()
+			.unwrap()
+	};
+	let trust_ls = || {
+		let output = xdg.cmd().args(["trust", "ls"]).output().unwrap();
+		assert!(output.status.success());
+		String::from_utf8(output.stdout).unwrap()
+	};
+
+	let output = exec();
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("isn't allowed"));
+	assert_eq!(trust_ls(), "");
+
+	xdg.allow(&project_dir);
+	assert!(exec().status.success());
+	assert_eq!(trust_ls(), format!("{},allowed\n", cfg_file.display()));
+
+	// Changing the config or its rc file requires allowing it again.
+	fs::write(project_dir.join(".cleanroom.rc.sh"), "echo hi\n").unwrap();
+	let output = exec();
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("changed since it was allowed"));
+	assert_eq!(trust_ls(), format!("{},changed\n", cfg_file.display()));
+	xdg.allow(&project_dir);
+	assert!(exec().status.success());
+
+	let status = xdg
+		.cmd()
+		.current_dir(&project_dir)
+		.arg("deny")
+		.status()
+		.unwrap();
+	assert!(status.success());
+	assert!(!exec().status.success());
+	assert_eq!(trust_ls(), "");
+}
+
+#[test]
+fn named() {
+	// Environments in the config home don't need to be allowed.
+	let xdg = Xdg::new();
+	xdg.new_script_env("named", "");
+	let output = xdg
+		.cmd()
+		.args(["exec", "named", "--", "/bin/true"])
+		.output()
+		.unwrap();
+	assert!(output.status.success());
+}
+
+#[test]
+fn old_version() {
+	// Older project configs are used without rewriting them, which would also
+	// invalidate the allowed hash.
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(&project_dir).unwrap();
+	let cfg_file = project_dir.join(".cleanroom.toml");
+	let src = "[shell]\nbin = \"/bin/bash\"\ninteractive = false\n\
+			   [bin]\ncoreutils = false\n";
+	fs::write(&cfg_file, src).unwrap();
+	xdg.allow(&project_dir);
+
+	for _ in 0..2 {
+		let output = xdg
+			.cmd()
+			.current_dir(&project_dir)
+			.args(["exec", ".", "--", "/bin/true"])
+			.output()
+			.unwrap();
+		assert!(output.status.success());
+	}
+	assert_eq!(fs::read_to_string(&cfg_file).unwrap(), src);
+
+	let output = xdg.cmd().args(["trust", "ls"]).output().unwrap();
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with(",allowed\n"));
+}
+
+#[test]
+fn sourced_files() {
+	// Files sourced through the config need the config to be allowed again
+	// when they change.
+	let xdg = Xdg::new();
+	let project_dir = xdg.root.join("project");
+	fs::create_dir_all(&project_dir).unwrap();
+	fs::write(
+		project_dir.join(".cleanroom.toml"),
+		"version = 1\n[shell]\nbin = \"/bin/bash\"\ninteractive = false\n\
+		 home = \"private\"\nrc.source = [\"setup.sh\"]\n\
+		 [vars]\ndotenv = [\".env\"]\n\
+		 [files]\n\".bashrc\" = { template = \"bashrc\" }\n\
+		 \"tool\" = { copy = \"tool\" }\n[bin]\ncoreutils = false\n",
+	)
+	.unwrap();
+	fs::write(project_dir.join("setup.sh"), "true\n").unwrap();
+	fs::write(project_dir.join(".env"), "FOO=bar\n").unwrap();
+	fs::write(project_dir.join("bashrc"), "true\n").unwrap();
+	fs::write(project_dir.join("tool"), "true\n").unwrap();
+	xdg.allow(&project_dir);
+	let exec = || {
+		xdg.cmd()
+			.current_dir(&project_dir)
+			.args(["exec", ".", "--", "/bin/true"])
+			.output()
+			.unwrap()
+			.status
+	};
+	assert!(exec().success());
+
+	fs::write(project_dir.join("setup.sh"), "echo pwned\n").unwrap();
+	assert!(!exec().success());
+	xdg.allow(&project_dir);
+	assert!(exec().success());
+
+	fs::write(project_dir.join(".env"), "BASH_ENV=pwned.sh\n").unwrap();
+	assert!(!exec().success());
+	xdg.allow(&project_dir);
+	assert!(exec().success());
+
+	// So are the sources of files written into the environment.
+	for file in ["bashrc", "tool"] {
+		fs::write(project_dir.join(file), "echo pwned\n").unwrap();
+		assert!(!exec().success(), "{file}");
+		xdg.allow(&project_dir);
+		assert!(exec().success(), "{file}");
+	}
+}
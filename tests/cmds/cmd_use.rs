@@ -0,0 +1,706 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::io::Write;
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::PermissionsExt;
+use std::path;
+use std::process;
+use std::thread;
+use std::time;
+
+use crate::common::env::Xdg;
+
+// Debug builds print the config to stdout before starting the shell, so only
+// the end of stdout is compared.
+
+#[test]
+fn set() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("set", "[vars.set]\nFOO = \"bar\"\n");
+
+	let output = xdg.use_env(&mut xdg.cmd(), "set", "echo \"$FOO\"");
+
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nbar\n"));
+}
+
+#[test]
+fn from_file() {
+	let xdg = Xdg::new();
+	let token = xdg.root.join("token");
+	xdg.new_script_env(
+		"from_file",
+		&format!("[vars.from_file]\nTOKEN = {token:?}\n"),
+	);
+	fs::write(&token, "secret\n").unwrap();
+
+	fs::set_permissions(&token, fs::Permissions::from_mode(0o644)).unwrap();
+	let output = xdg.use_env(&mut xdg.cmd(), "from_file", "echo \"$TOKEN\"");
+	assert!(!output.status.success());
+
+	fs::set_permissions(&token, fs::Permissions::from_mode(0o600)).unwrap();
+	let output = xdg.use_env(&mut xdg.cmd(), "from_file", "echo \"$TOKEN\"");
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nsecret\n"));
+}
+
+#[test]
+fn from_cmd() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"from_cmd",
+		"[vars.from_cmd]\nA = \"echo '  a  '\"\n\
+		 B = { cmd = \"echo '  b  '\", trim = \"all\" }\n\
+		 C = { cmd = \"exec sleep 5\", timeout = 1 }\n",
+	);
+
+	let output = xdg.use_env(&mut xdg.cmd(), "from_cmd", "");
+	let stderr = std::str::from_utf8(&output.stderr).unwrap();
+	assert!(!output.status.success());
+	assert!(stderr.contains("didn't finish within 1s"));
+
+	let cfg = fs::read_to_string(xdg.cfg_file("from_cmd")).unwrap();
+	fs::write(xdg.cfg_file("from_cmd"), cfg.replace("C = ", "# C = ")).unwrap();
+	let output =
+		xdg.use_env(&mut xdg.cmd(), "from_cmd", "echo \"[$A]\" \"[$B]\"");
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\n[  a  ] [b]\n"));
+}
+
+#[test]
+fn timeout_prompt() {
+	// Commands with a timeout can still prompt on the terminal.
+	let Some(script) = ["/bin/script", "/usr/bin/script"]
+		.iter()
+		.find(|bin| path::Path::new(bin).is_file())
+	else {
+		eprintln!("Skipping, script isn't installed");
+		return;
+	};
+
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"prompt",
+		"[vars.from_cmd]\n\
+		 ANSWER = { cmd = \"read x; echo \\\"got $x\\\"\", timeout = 5 }\n",
+	);
+
+	// Run `cr env` on a new terminal with `script`.
+	let cr = xdg.cmd();
+	let mut child = process::Command::new(script)
+		.env_clear()
+		.envs(cr.get_envs().filter_map(|(var, val)| Some((var, val?))))
+		.arg("-qec")
+		.arg(format!(
+			"'{}' env prompt",
+			path::Path::new(cr.get_program()).display()
+		))
+		.arg("/dev/null")
+		.stdin(process::Stdio::piped())
+		.stdout(process::Stdio::piped())
+		.spawn()
+		.unwrap();
+	child.stdin.take().unwrap().write_all(b"answer\n").unwrap();
+	let output = child.wait_with_output().unwrap();
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.contains("ANSWER='got answer'"));
+}
+
+#[test]
+fn timeout_children() {
+	// Processes started by a timed out command are killed with it.
+	let xdg = Xdg::new();
+	let marker = xdg.root.join("marker");
+	let cmd = format!(
+		"{{ cmd = \"(sleep 2; touch {}); true\", timeout = 1 }}",
+		marker.display()
+	);
+	xdg.new_script_env(
+		"timeout_children",
+		&format!("[vars.from_cmd]\nC = {cmd}\n[hooks]\npre_enter = [{cmd}]"),
+	);
+
+	let output = xdg.use_env(&mut xdg.cmd(), "timeout_children", "");
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("didn't finish within 1s"));
+
+	let cfg = fs::read_to_string(xdg.cfg_file("timeout_children")).unwrap();
+	fs::write(
+		xdg.cfg_file("timeout_children"),
+		cfg.replace("C = ", "# C = "),
+	)
+	.unwrap();
+	let output = xdg.use_env(&mut xdg.cmd(), "timeout_children", "");
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("Hook 'pre_enter' command"));
+
+	thread::sleep(time::Duration::from_secs(3));
+	assert!(!marker.exists());
+}
+
+#[test]
+fn dotenv() {
+	let xdg = Xdg::new();
+	let dotenv = xdg.root.join(".env");
+	xdg.new_script_env(
+		"dotenv",
+		&format!("[vars]\ndotenv = [{dotenv:?}]\n[vars.set]\nC = \"set\"\n"),
+	);
+	fs::write(
+		&dotenv,
+		"# Comment\nexport A=plain value # comment\n\
+		 B=\"multi\nline\\t\\\"quoted\\\"\"\nC='from dotenv'\n",
+	)
+	.unwrap();
+
+	let output = xdg.use_env(
+		&mut xdg.cmd(),
+		"dotenv",
+		"printf '%s|' \"$A\" \"$B\" \"$C\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nplain value|multi\nline\t\"quoted\"|set|"));
+
+	fs::write(&dotenv, "A=1\nB='unterminated\n").unwrap();
+	let output = xdg.use_env(&mut xdg.cmd(), "dotenv", "");
+	let stderr = std::str::from_utf8(&output.stderr).unwrap();
+	assert!(!output.status.success());
+	assert!(stderr.contains("Unterminated single quote"));
+	assert!(stderr.contains(".env:2:3"));
+}
+
+#[test]
+fn inherit_patterns() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"patterns",
+		"[vars]\ninherit = [\"CR_T_*\", \"CR_X\"]\ndeny = [\"CR_T_DENY*\"]\n",
+	);
+	let script = "printf '%s|' \"$CR_T_A\" \"$CR_T_DENY\" \"$CR_X\" \"$CR_Y\"";
+
+	let output = xdg.use_env(
+		xdg.cmd()
+			.env("CR_T_A", "a")
+			.env("CR_T_DENY", "d")
+			.env("CR_X", "x")
+			.env("CR_Y", "y"),
+		"patterns",
+		script,
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\na||x||"));
+
+	xdg.new_script_env(
+		"all",
+		"[vars]\ninherit_all = true\ndeny = [\"CR_T_DENY*\"]\n",
+	);
+	let output = xdg.use_env(
+		xdg.cmd()
+			.env("CR_T_A", "a")
+			.env("CR_T_DENY", "d")
+			.env("CR_X", "x")
+			.env("CR_Y", "y"),
+		"all",
+		script,
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\na||x|y|"));
+}
+
+#[test]
+fn inherit_tables() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"tables",
+		"[vars]\ninherit = [\n\
+		 \t{ name = \"CR_EDITOR\", default = \"vi\" },\n\
+		 \t{ name = \"CR_SOCK\", required = false },\n\
+		 \t{ name = \"CR_TOKEN\", message = \"export it from the vault\" },\n\
+		 \t\"CR_OTHER\",\n]\n",
+	);
+	let script = "printf '%s|' \"$CR_EDITOR\" \"$CR_SOCK\" \"$CR_TOKEN\"";
+
+	let output = xdg.use_env(&mut xdg.cmd(), "tables", script);
+	let stderr = std::str::from_utf8(&output.stderr).unwrap();
+	assert!(!output.status.success());
+	assert!(stderr.contains("\n  CR_TOKEN: export it from the vault\n"));
+	assert!(stderr.contains("\n  CR_OTHER\n"));
+
+	let output = xdg.use_env(
+		xdg.cmd().env("CR_TOKEN", "t").env("CR_OTHER", "o"),
+		"tables",
+		script,
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nvi||t|"));
+}
+
+#[test]
+fn inherit_rename() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"rename",
+		"[vars.inherit_rename]\nCR_GH_WORK = \"GITHUB_TOKEN\"\n\
+		 CR_MISSING = { name = \"OTHER\", message = \"set it\" }\n",
+	);
+
+	let output = xdg.use_env(
+		xdg.cmd().env("CR_GH_WORK", "gh"),
+		"rename",
+		"printf '%s|' \"$GITHUB_TOKEN\" \"$CR_GH_WORK\"",
+	);
+	let stderr = std::str::from_utf8(&output.stderr).unwrap();
+	assert!(!output.status.success());
+	assert!(stderr.contains("\n  CR_MISSING (as OTHER): set it\n"));
+
+	let output = xdg.use_env(
+		xdg.cmd().env("CR_GH_WORK", "gh").env("CR_MISSING", "m"),
+		"rename",
+		"printf '%s|' \"$GITHUB_TOKEN\" \"$CR_GH_WORK\" \"$OTHER\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\ngh||m|"));
+}
+
+#[test]
+fn prepend_append() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"lists",
+		"[vars]\ninherit = [\"CR_LIST\"]\n\
+		 [vars.prepend]\nCR_LIST = [\"/a\", \"/b\"]\n\
+		 PATH = { entries = [\"/nonexistent\", \"/usr/bin\"], \
+		 existing_only = true }\n\
+		 [vars.append]\n\
+		 CR_LIST = { entries = [\"/a\", \"/c\"], separator = \";\" }\n",
+	);
+
+	let output = xdg.use_env(
+		xdg.cmd().env("CR_LIST", "/b;/d"),
+		"lists",
+		"printf '%s|' \"$CR_LIST\" \"$PATH\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\n/a;/b;/d;/c|/usr/bin:/usr/local/bin:/bin|"));
+}
+
+#[test]
+fn non_unicode() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"bytes",
+		"[vars]\ninherit = [\"CR_LATIN1\"]\n\
+		 [vars.set]\nCR_ESCAPED = { escaped = 'caf\\xE9 \\\\' }\n",
+	);
+
+	let output = xdg.use_env(
+		xdg.cmd().env("CR_LATIN1", OsStr::from_bytes(b"na\xefve")),
+		"bytes",
+		"printf '%s|' \"$CR_LATIN1\" \"$CR_ESCAPED\"",
+	);
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nna\xefve|caf\xe9 \\|"));
+}
+
+#[test]
+fn non_unicode_paths() {
+	let xdg = Xdg::new();
+	xdg.new_env("paths");
+	fs::write(
+		xdg.cfg_file("paths"),
+		"version = 1\n\
+		 [files]\ncopied = { copy = { escaped = 'caf\\xE9' } }\n\
+		 [shell]\nbin = \"/bin/bash\"\ninteractive = false\n\
+		 home = \"private\"\n\
+		 [bin]\ncoreutils = false\n\
+		 inherit_dirs = [\"/bin\", { escaped = '/opt/caf\\xE9' }]\n",
+	)
+	.unwrap();
+	let cfg_dir = xdg.cfg_file("paths").parent().unwrap().to_owned();
+	fs::write(cfg_dir.join(OsStr::from_bytes(b"caf\xe9")), "copied|").unwrap();
+
+	let output = xdg.use_env(
+		&mut xdg.cmd(),
+		"paths",
+		"cat \"$HOME/copied\"; printf '%s' \"$PATH\"",
+	);
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"copied|/bin:/opt/caf\xe9"));
+}
+
+#[test]
+fn baseline() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"baseline",
+		"[vars]\nbaseline = \"terminal\"\ndeny = [\"LC_*\"]\n",
+	);
+
+	let output = xdg.use_env(
+		xdg.cmd()
+			.env("TERM", "xterm")
+			.env("LC_TIME", "C")
+			.env("DISPLAY", ":0"),
+		"baseline",
+		"printf '%s|' \"$SHELL\" \"$TERM\" \"$LC_TIME\" \"$DISPLAY\" \
+		 \"${HOME:+home}\" \"${USER:+user}\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\n/bin/bash|xterm|||home|user|"));
+}
+
+#[test]
+fn cleanroom_vars() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("outer", "");
+	xdg.new_script_env("hidden", "[vars]\ncleanroom_vars = false\n");
+
+	let output = xdg.use_env(
+		xdg.cmd().env("CLEANROOM_DEPTH", "1"),
+		"outer",
+		"printf '%s|' \"$CLEANROOM_ENV\" \"$CLEANROOM_DEPTH\" \
+		 \"${CLEANROOM_SESSION:+session}\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nouter|2|session|"));
+
+	let output = xdg.use_env(
+		&mut xdg.cmd(),
+		"hidden",
+		"printf '%s|' \"$CLEANROOM_ENV\"",
+	);
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\n|"));
+}
+
+#[test]
+fn nested() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("inner", "");
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "inner", "shell.nested", "deny"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	let output = xdg.use_env(
+		xdg.cmd()
+			.env("CLEANROOM_ENV", "outer")
+			.env("CLEANROOM_DEPTH", "1"),
+		"inner",
+		"echo started",
+	);
+	assert!(!output.status.success());
+	assert!(!output.stdout.ends_with(b"started\n"));
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("Already in environment 'outer'"));
+}
+
+#[test]
+fn files() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"files",
+		"[vars.set]\nNAME = \"cleanroom\"\n\
+		 [files]\n\".gitconfig\" = { content = \"name = ${NAME} $$5\" }\n\
+		 \".npmrc\" = { template = \"npmrc\" }\n\
+		 \"tool\" = { copy = \"tool\" }\n",
+	);
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "files", "shell.home", "private"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	let cfg_dir = xdg.cfg_file("files").parent().unwrap().to_owned();
+	fs::write(cfg_dir.join("npmrc"), "user=${USER_NPM}\n").unwrap();
+	fs::write(cfg_dir.join("tool"), "#!/bin/sh\n").unwrap();
+	fs::set_permissions(
+		cfg_dir.join("tool"),
+		fs::Permissions::from_mode(0o750),
+	)
+	.unwrap();
+
+	// The template uses a variable which isn't set.
+	let output = xdg.use_env(&mut xdg.cmd(), "files", "true");
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("file '.npmrc': Variable 'USER_NPM' isn't set"));
+
+	fs::write(cfg_dir.join("npmrc"), "user=${NAME}\n").unwrap();
+	let output = xdg.use_env(
+		&mut xdg.cmd(),
+		"files",
+		"cat \"$HOME/.gitconfig\" \"$HOME/.npmrc\"",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nname = cleanroom $5user=cleanroom\n"));
+
+	// Rendered files are private, copies keep the mode of the source.
+	let home_dir = xdg.data_home.join("cleanroom/files/home");
+	let mode = |file: &str| {
+		fs::metadata(home_dir.join(file))
+			.unwrap()
+			.permissions()
+			.mode() & 0o777
+	};
+	assert_eq!(mode(".gitconfig"), 0o600);
+	assert_eq!(mode(".npmrc"), 0o600);
+	assert_eq!(mode("tool"), 0o750);
+
+	// Removed entries are deleted on the next use.
+	let status = xdg
+		.cmd()
+		.args(["config", "unset", "files", "files.\".npmrc\""])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	let output = xdg.use_env(&mut xdg.cmd(), "files", "true");
+	assert!(output.status.success());
+	assert!(home_dir.join(".gitconfig").is_file());
+	assert!(!home_dir.join(".npmrc").exists());
+}
+
+#[test]
+fn files_user_home() {
+	// Files are never written to the user's home directory or over files
+	// which cleanroom didn't write.
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"user_home",
+		"[files]\n\".gitconfig\" = { content = \"env\" }\n",
+	);
+	let user_home = xdg.root.join("user_home");
+	fs::create_dir(&user_home).unwrap();
+	fs::write(user_home.join(".gitconfig"), "user").unwrap();
+
+	let set_home = |home: &str| {
+		let status = xdg
+			.cmd()
+			.args(["config", "set", "user_home", "shell.home", home])
+			.status()
+			.unwrap();
+		assert!(status.success());
+	};
+	for home in ["~", user_home.to_str().unwrap()] {
+		set_home(home);
+		let output =
+			xdg.use_env(xdg.cmd().env("HOME", &user_home), "user_home", "true");
+		assert!(!output.status.success(), "{home}");
+		assert!(std::str::from_utf8(&output.stderr)
+			.unwrap()
+			.contains("relative to the shell's home directory"));
+	}
+	assert_eq!(fs::read(user_home.join(".gitconfig")).unwrap(), b"user");
+
+	set_home("private");
+	let home_dir = xdg.data_home.join("cleanroom/user_home/home");
+	fs::create_dir_all(&home_dir).unwrap();
+	fs::write(home_dir.join(".gitconfig"), "user").unwrap();
+	let output = xdg.use_env(&mut xdg.cmd(), "user_home", "true");
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("since it wasn't written by cleanroom"));
+	assert_eq!(fs::read(home_dir.join(".gitconfig")).unwrap(), b"user");
+
+	fs::remove_file(home_dir.join(".gitconfig")).unwrap();
+	for _ in 0..2 {
+		let output = xdg.use_env(&mut xdg.cmd(), "user_home", "true");
+		assert!(output.status.success());
+	}
+	assert_eq!(fs::read(home_dir.join(".gitconfig")).unwrap(), b"env");
+}
+
+#[test]
+fn rc() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"rc",
+		"[shell.rc]\nsnippets = [\"RC_VAR=snippet\"]\n\
+		 [shell.rc.aliases]\ngreet = \"echo 'hi there'\"\n\
+		 [shell.rc.functions]\nshout = \"echo \\\"$1!\\\"\"\n",
+	);
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "rc", "shell.interactive", "true"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	let cfg_dir = xdg.cfg_file("rc").parent().unwrap().to_owned();
+	fs::write(cfg_dir.join("rc.sh"), "USER_RC=$RC_VAR\n").unwrap();
+
+	let output = xdg.use_env(
+		&mut xdg.cmd(),
+		"rc",
+		"greet\nshout hey\necho \"$USER_RC\"\n",
+	);
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with("\nhi there\nhey!\nsnippet\n"));
+}
+
+#[test]
+fn hooks() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"hooks",
+		"[hooks]\n\
+		 pre_enter = [\"echo pre > \\\"$CLEANROOM_DATA_DIR/pre\\\"\"]\n\
+		 post_exit = [\
+		 { cmd = \"false\", on_failure = \"ignore\" }, \
+		 \"echo $CLEANROOM_EXIT_CODE > \\\"$CLEANROOM_DATA_DIR/code\\\"\"]\n",
+	);
+	let data_dir = xdg.data_home.join("cleanroom/hooks");
+
+	let output = xdg.use_env(&mut xdg.cmd(), "hooks", "exit 3");
+	assert!(output.status.success());
+	assert_eq!(fs::read_to_string(data_dir.join("pre")).unwrap(), "pre\n");
+	assert_eq!(fs::read_to_string(data_dir.join("code")).unwrap(), "3\n");
+
+	// A failing `pre_enter` doesn't start the shell.
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "hooks", "hooks.pre_enter", "[\"exit 1\"]"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+	let output = xdg.use_env(&mut xdg.cmd(), "hooks", "echo started");
+	assert!(!output.status.success());
+	assert!(!output.stdout.ends_with(b"started\n"));
+}
+
+#[test]
+fn post_enter() {
+	let xdg = Xdg::new();
+	xdg.new_script_env(
+		"post_enter",
+		"[hooks]\npost_enter = [\"AGENT=started\", \
+		 { cmd = \"false\", on_failure = \"warn\" }]\n",
+	);
+	let status = xdg
+		.cmd()
+		.args(["config", "set", "post_enter", "shell.interactive", "true"])
+		.status()
+		.unwrap();
+	assert!(status.success());
+
+	let output = xdg.use_env(&mut xdg.cmd(), "post_enter", "echo $AGENT\n");
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\nstarted\n"));
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("post_enter hook 'false' failed"));
+}
+
+#[test]
+fn cwd() {
+	let xdg = Xdg::new();
+	xdg.new_script_env("cwd", "");
+	let set = |key: &str, val: &str| {
+		let status = xdg
+			.cmd()
+			.args(["config", "set", "cwd", key, val])
+			.status()
+			.unwrap();
+		assert!(status.success());
+	};
+	set("shell.cwd", "${CLEANROOM_DATA_DIR}/bin");
+	let data_dir = xdg.data_home.join("cleanroom/cwd");
+
+	let output = xdg.use_env(&mut xdg.cmd(), "cwd", "pwd");
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with(&format!("\n{}\n", data_dir.join("bin").display())));
+
+	let output = xdg.use_env_args(&mut xdg.cmd(), &["--cd", "/", "cwd"], "pwd");
+	assert!(output.status.success());
+	assert!(output.stdout.ends_with(b"\n/\n"));
+	let output =
+		xdg.use_env_args(&mut xdg.cmd(), &["--cd", "/nonexistent", "cwd"], "");
+	assert!(!output.status.success());
+
+	// Only starts within `cwd` or `cwd_allowed`.
+	set("shell.cwd_policy", "restrict");
+	let output = xdg.use_env(xdg.cmd().current_dir("/"), "cwd", "pwd");
+	assert!(!output.status.success());
+
+	let output = xdg.use_env(xdg.cmd().current_dir(&data_dir), "cwd", "pwd");
+	assert!(!output.status.success());
+	set("shell.cwd_allowed", "[\"${CLEANROOM_DATA_DIR}\"]");
+	let output = xdg.use_env(xdg.cmd().current_dir(&data_dir), "cwd", "pwd");
+	assert!(output.status.success());
+	assert!(std::str::from_utf8(&output.stdout)
+		.unwrap()
+		.ends_with(&format!("\n{}\n", data_dir.display())));
+}
+
+#[test]
+fn cwd_restrict() {
+	// Nothing is written for a shell which isn't allowed to start.
+	let xdg = Xdg::new();
+	let data_dir = xdg.data_home.join("cleanroom/restrict");
+	xdg.new_script_env(
+		"restrict",
+		&format!(
+			"[files]\n\"{}\" = {{ content = \"\" }}",
+			data_dir.join("file").display()
+		),
+	);
+	for (key, val) in [
+		("shell.cwd", "${CLEANROOM_DATA_DIR}"),
+		("shell.cwd_policy", "restrict"),
+	] {
+		let status = xdg
+			.cmd()
+			.args(["config", "set", "restrict", key, val])
+			.status()
+			.unwrap();
+		assert!(status.success());
+	}
+
+	fs::remove_dir(data_dir.join("bin")).unwrap();
+
+	let output = xdg.use_env(xdg.cmd().current_dir("/"), "restrict", "pwd");
+	assert!(!output.status.success());
+	assert!(std::str::from_utf8(&output.stderr)
+		.unwrap()
+		.contains("Not starting the shell in '/'"));
+	assert!(!data_dir.join("bin").exists());
+	assert!(!data_dir.join("file").exists());
+}
@@ -1 +1,14 @@
+mod cmd_check;
+mod cmd_config;
+mod cmd_edit;
+mod cmd_env;
+mod cmd_exec;
+mod cmd_history;
+mod cmd_home;
+mod cmd_hook;
 mod cmd_ls;
+mod cmd_migrate;
+mod cmd_rm;
+mod cmd_schema;
+mod cmd_trust;
+mod cmd_use;
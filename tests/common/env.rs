@@ -1 +1,108 @@
+use std::fs;
+use std::io::Write;
+use std::path;
+use std::process;
 
+use rand::distributions::{Alphanumeric, DistString};
+
+use crate::BIN_NAME;
+
+/// XDG directories unique to a test, removed when dropped.
+pub struct Xdg {
+	pub root: path::PathBuf,
+	pub cfg_home: path::PathBuf,
+	pub data_home: path::PathBuf,
+}
+
+impl Xdg {
+	pub fn new() -> Self {
+		let id = Alphanumeric.sample_string(&mut rand::thread_rng(), 16);
+		let root = path::PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(id);
+		let cfg_home = root.join("config");
+		let data_home = root.join("local").join("share");
+
+		Self {
+			root,
+			cfg_home,
+			data_home,
+		}
+	}
+
+	/// A `cr` command which only sees this test's XDG directories.
+	pub fn cmd(&self) -> process::Command {
+		let mut cmd = process::Command::new(BIN_NAME);
+		cmd.env_clear()
+			.env("XDG_CONFIG_HOME", &self.cfg_home)
+			.env("XDG_DATA_HOME", &self.data_home);
+		cmd
+	}
+
+	/// Create an environment with `cr new`.
+	pub fn new_env(&self, name: &str) {
+		let status = self.cmd().args(["new", name]).output().unwrap().status;
+		assert!(status.success());
+	}
+
+	/// Create an environment with a non-interactive bash and `cfg` appended to
+	/// its config, for running scripts with `use_env`.
+	pub fn new_script_env(&self, name: &str, cfg: &str) {
+		self.new_env(name);
+		fs::write(
+			self.cfg_file(name),
+			format!(
+				"version = 1\n{cfg}\n[shell]\nbin = \"/bin/bash\"\n\
+				 interactive = false\n[bin]\ncoreutils = false\n"
+			),
+		)
+		.unwrap();
+	}
+
+	/// Allow the config of the project-local environment of `dir`.
+	pub fn allow(&self, dir: &path::Path) {
+		let status = self.cmd().arg("allow").arg(dir).output().unwrap().status;
+		assert!(status.success());
+	}
+
+	/// Run `script` in the environment with `cr use`.
+	pub fn use_env(
+		&self,
+		cmd: &mut process::Command,
+		name: &str,
+		script: &str,
+	) -> process::Output {
+		self.use_env_args(cmd, &[name], script)
+	}
+
+	/// Like `use_env` with `args` for `cr use`, which include the name.
+	pub fn use_env_args(
+		&self,
+		cmd: &mut process::Command,
+		args: &[&str],
+		script: &str,
+	) -> process::Output {
+		let mut child = cmd
+			.arg("use")
+			.args(args)
+			.stdin(process::Stdio::piped())
+			.stdout(process::Stdio::piped())
+			.stderr(process::Stdio::piped())
+			.spawn()
+			.unwrap();
+		// `cr` doesn't read the script if it fails before starting the shell.
+		let _ = child.stdin.take().unwrap().write_all(script.as_bytes());
+		child.wait_with_output().unwrap()
+	}
+
+	pub fn cfg_file(&self, name: &str) -> path::PathBuf {
+		self.cfg_home
+			.join("cleanroom")
+			.join(name)
+			.join("config.toml")
+	}
+}
+
+impl Drop for Xdg {
+	fn drop(&mut self) {
+		let _ = fs::remove_dir_all(&self.root);
+	}
+}